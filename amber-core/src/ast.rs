@@ -1,3 +1,5 @@
+use std::fmt;
+
 #[derive(Debug, Clone)]
 pub enum Op {
     Add,
@@ -5,25 +7,242 @@ pub enum Op {
     Mul,
     Div,
     LessThan,
+    Equal,
+    NotEqual,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Int,
+    Str,
+    Void,
+    Array(Box<Type>), // Element Type
+    Named(String),    // Class or Interface Name
+    Generic(String, Vec<Type>),     // Generic Class Name, Type Arguments: Box<int>
+    Param(String),                  // Type parameter of the enclosing generic class or function
+    Function(Vec<Type>, Box<Type>), // Parameter Types, Return Type
+    Nullable(Box<Type>), // Point? : the type or null
+    Tuple(Vec<Type>),    // Element Types: (int, String)
+    Null,             // Type of the 'null' literal
+    Infer,            // 'var': taken from the initializer
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::Str => write!(f, "String"),
+            Type::Void => write!(f, "void"),
+            Type::Array(elem) => write!(f, "{}[]", elem),
+            Type::Named(name) | Type::Param(name) => write!(f, "{}", name),
+            Type::Generic(name, args) => {
+                let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+                write!(f, "{}<{}>", name, args.join(", "))
+            }
+            Type::Function(params, ret) => {
+                let params: Vec<String> = params.iter().map(|p| p.to_string()).collect();
+                write!(f, "func({}) -> {}", params.join(", "), ret)
+            }
+            Type::Nullable(inner) if matches!(**inner, Type::Function(..)) => write!(f, "({})?", inner),
+            Type::Nullable(inner) => write!(f, "{}?", inner),
+            Type::Tuple(elems) => {
+                let elems: Vec<String> = elems.iter().map(|e| e.to_string()).collect();
+                write!(f, "({})", elems.join(", "))
+            }
+            Type::Null => write!(f, "null"),
+            Type::Infer => write!(f, "var"),
+        }
+    }
+}
+
+impl Type {
+    // Class or interface behind a named or generic type
+    pub fn class_name(&self) -> Option<&str> {
+        match self {
+            Type::Named(name) | Type::Generic(name, _) => Some(name),
+            _ => None,
+        }
+    }
+
+    // The type that also admits null (unchanged if it already does)
+    pub fn nullable(self) -> Type {
+        match self {
+            Type::Nullable(_) | Type::Null => self,
+            ty => Type::Nullable(Box::new(ty)),
+        }
+    }
+
+    // The type without null: what a null check narrows a value to
+    pub fn non_null(&self) -> Type {
+        match self {
+            Type::Nullable(inner) => (**inner).clone(),
+            ty => ty.clone(),
+        }
+    }
+
+    pub fn is_nullable(&self) -> bool {
+        matches!(self, Type::Nullable(_) | Type::Null)
+    }
+
+    // Replaces type parameters by the types bound to them (generics are erased: this is only for checking)
+    pub fn substitute(&self, bindings: &[(String, Type)]) -> Type {
+        match self {
+            Type::Param(name) => bindings.iter().find(|(p, _)| p == name).map_or_else(|| self.clone(), |(_, ty)| ty.clone()),
+            Type::Array(elem) => Type::Array(Box::new(elem.substitute(bindings))),
+            Type::Nullable(inner) => inner.substitute(bindings).nullable(),
+            Type::Generic(name, args) => Type::Generic(name.clone(), args.iter().map(|a| a.substitute(bindings)).collect()),
+            Type::Tuple(elems) => Type::Tuple(elems.iter().map(|e| e.substitute(bindings)).collect()),
+            Type::Function(params, ret) => {
+                Type::Function(params.iter().map(|p| p.substitute(bindings)).collect(), Box::new(ret.substitute(bindings)))
+            }
+            _ => self.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Visibility {
+    Public,    // Default: reachable from anywhere
+    Private,   // Declaring class only (declaring file, for top-level functions)
+    Protected, // Declaring class and its subclasses
+}
+
+impl fmt::Display for Visibility {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Visibility::Public => write!(f, "public"),
+            Visibility::Private => write!(f, "private"),
+            Visibility::Protected => write!(f, "protected"),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum Expr {
     Integer(i32),
     StringLiteral(String),
+    Null,
     Variable(String),
-    NewArray(Box<Expr>), // Size
+    NewArray(Type, Box<Expr>), // Element Type, Size
     ArrayAccess(String, Box<Expr>), // Name, Index
     Call(String, Vec<Expr>),
     MethodCall(Box<Expr>, String, Vec<Expr>), // Object, Method Name, Args
-    NewInstance(String), // Class Name
+    NewInstance(String, Vec<Type>, Vec<Expr>), // Class Name, Type Args (inferred if empty), Constructor Args
     GetField(Box<Expr>, String), // Object Expr, Field Name
+    SafeGetField(Box<Expr>, String), // obj?.field : null if obj is null
+    SafeMethodCall(Box<Expr>, String, Vec<Expr>), // obj?.method(args) : null if obj is null
+    Coalesce(Box<Expr>, Box<Expr>), // value ?? default
+    Conditional(Box<Expr>, Box<Expr>, Box<Expr>), // cond ? then : else
+    InstanceOf(Box<Expr>, Type), // value is Class
+    Cast(Box<Expr>, Type), // value as Class : checked at runtime
+    Tuple(Vec<Expr>), // (1, "a") : two or more elements
+    TupleElement(Box<Expr>, usize), // pair.0
+    NamedArg(String, Box<Expr>), // level: 3, only in the arguments of a call
+    VarArgs(Vec<Expr>), // Internal: the arguments packed into a variadic parameter's array
+    // Generated for records only, with no source syntax; lowered according to the values' types
+    ValueEquals(Box<Expr>, Box<Expr>), // Structural equality of two values of one type
+    ValueHash(Vec<Expr>), // Combined hash of the values, consistent with ValueEquals
+    ValueText(Box<Expr>), // The value as a String
+    SuperCall(Option<String>, Vec<Expr>), // Parent Method (None = parent constructor), Args
     Binary(Box<Expr>, Op, Box<Expr>),
+    Match(Box<Expr>, Vec<MatchArm>), // Scrutinee, Arms tried in order
+    Lambda(Vec<Param>, Vec<Stmt>), // Params, Body (an expression body is a single return)
+    CallValue(Box<Expr>, Vec<Expr>), // Function Value, Args: make(1)(2)
+}
+
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    Integer(i32),
+    StringLiteral(String),
+    Variant(String, String, Vec<String>), // Enum, Variant, Payload Bindings ('_' skips a field)
+    Binding(String),                      // Catch-all; binds the value unless the name is '_'
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub body: Stmt, // Stmt::Expression yields the arm's value, a Block yields nothing
+}
+
+#[derive(Debug, Clone)]
+pub struct Param {
+    pub name: String,
+    pub ty: Type,
+    pub mutable: bool, // Parameters are read-only unless declared 'mut'
+    pub default: Option<Expr>, // int level = 1: a compile-time value used when the argument is left out
+    pub variadic: bool, // int... xs: the last parameter, an int[] holding the remaining arguments
+}
+
+#[derive(Debug, Clone)]
+pub struct Field {
+    pub name: String,
+    pub ty: Type,
+    pub is_final: bool,
+    pub is_static: bool, // One class-scoped global instead of a per-instance slot
+    pub visibility: Visibility,
+    pub initializer: Option<Expr>, // Run at the start of every construction (once, for static fields)
+}
+
+#[derive(Debug, Clone)]
+pub struct FunctionDecl {
+    pub name: String, // Mangled as Class_Method for methods
+    pub params: Vec<Param>, // Methods start with the implicit 'this'
+    pub return_type: Type,
+    pub body: Vec<Stmt>,
+}
+
+// A method an interface requires, without a body
+#[derive(Debug, Clone)]
+pub struct MethodSig {
+    pub name: String,
+    pub params: Vec<Param>,
+    pub return_type: Type,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClassDecl {
+    pub name: String,
+    pub type_params: Vec<String>, // class Box<T>
+    pub parent: Option<String>,
+    pub interfaces: Vec<String>,
+    pub fields: Vec<Field>,
+    pub methods: Vec<FunctionDecl>,
+    pub static_methods: Vec<FunctionDecl>, // No implicit 'this'
+    pub is_record: bool, // record Name(fields): constructor, equals, hashCode and toString are generated
+}
+
+#[derive(Debug, Clone)]
+pub struct InterfaceDecl {
+    pub name: String,
+    pub methods: Vec<MethodSig>,
+}
+
+#[derive(Debug, Clone)]
+pub struct EnumDecl {
+    pub name: String,
+    pub variants: Vec<VariantDecl>,
+}
+
+#[derive(Debug, Clone)]
+pub struct VariantDecl {
+    pub name: String,
+    pub value: Option<i32>, // Explicit '= value'
+    pub fields: Vec<Param>, // Payload: Circle(int r)
+}
+
+// catch (NotFound e) { ... }; a bare catch (e) catches any Exception
+#[derive(Debug, Clone)]
+pub struct CatchClause {
+    pub ty: Type,
+    pub name: String,
+    pub body: Vec<Stmt>,
 }
 
 #[derive(Debug, Clone)]
 pub enum Stmt {
-    VarDecl(String, Expr),
+    VarDecl(String, Type, Expr, bool), // Name, Declared Type, Initializer, Is Final
+    Destructure(Vec<String>, Expr, bool), // var (a, b) = tuple : Names ('_' skips an element), Tuple, Is Final
+    ConstDecl(String, Type, Expr),     // Name, Declared Type, Compile-time value
     Assign(String, Expr),
     Return(Expr),
     ArraySet(String, Expr, Expr), // Name, Index, Value
@@ -31,8 +250,16 @@ pub enum Stmt {
     Block(Vec<Stmt>),
     If(Expr, Box<Stmt>, Option<Box<Stmt>>), // Condition, Then, Else
     While(Expr, Box<Stmt>),                 // Condition, Body
+    Switch(Expr, Vec<(Vec<Expr>, Vec<Stmt>)>, Option<Vec<Stmt>>), // Subject, Cases (Values, Body), Default Body
+    Try(Vec<Stmt>, Vec<CatchClause>, Option<Vec<Stmt>>), // Body, Catches tried in order, Finally Body
+    Throw(Option<Expr>),                    // Exception; None rethrows the one being caught
+    Labeled(String, Box<Stmt>),             // Label, Loop
+    Break(Option<String>),                  // Optional target label
+    Continue(Option<String>),               // Optional target label
     Expression(Expr),
-    Function(String, Vec<String>, Vec<Stmt>), // Name, Params, Body
-    Class(String, Vec<String>, Vec<Stmt>), // Name, Fields, Methods
+    Function(FunctionDecl),
+    Class(ClassDecl),
+    Interface(InterfaceDecl),
+    Enum(EnumDecl),
     FieldSet(Box<Expr>, String, Expr), // Object, Field Name, Value
-}
\ No newline at end of file
+}
@@ -6,6 +6,7 @@ pub enum OpCode {
     Halt = 0x00,
     Jump = 0x01,
     JumpIfFalse = 0x02,
+    TableSwitch = 0x03,
 
     // --- Constants & Variables ---
     Push = 0x10,
@@ -17,6 +18,7 @@ pub enum OpCode {
     NewArray = 0x16,
     StoreArray = 0x17,
     LoadArray = 0x18,
+    ArrayLength = 0x19,
 
     // --- Arithmetic & Logic ---
     Add = 0x20,
@@ -24,17 +26,30 @@ pub enum OpCode {
     Mul = 0x22,
     Div = 0x23,
     Less = 0x24,
+    Equal = 0x25,
 
     // --- Object-Oriented ---
     NewInstance = 0x40,
     GetField = 0x41,
     SetField = 0x42,
+    Invoke = 0x43,
+    InstanceOf = 0x44,
+    CheckCast = 0x45,
 
     // --- Utilities ---
     Call = 0x30,
     Return = 0x31,
+    Enter = 0x32,
+    Throw = 0x33,
+    Rethrow = 0x34,
+    MakeClosure = 0x35,
+    CallIndirect = 0x36,
     Pop = 0x80,
     Print = 0x81,
+    Dup = 0x82,
+    IntToString = 0x83,
+    HashString = 0x84,
+    HashCombine = 0x85,
 }
 
 impl From<OpCode> for u8 {
@@ -2,30 +2,109 @@
 use std::fs::File;
 use std::io::{Write, BufWriter};
 use super::bytecode::OpCode;
-use crate::ast::{Expr, Op};
+use crate::ast::{Expr, Op, Param, Type, Visibility, Field, FunctionDecl, ClassDecl, InterfaceDecl, EnumDecl, MatchArm, Pattern, CatchClause};
 use crate::ast::Stmt;
-use crate::semant::{SymbolTable, ClassInfo};
-use std::collections::HashMap;
+use crate::semant::{self, SymbolTable, ModuleScope, ClassInfo, EnumInfo, VariantInfo, FieldInfo, FunctionInfo, InterfaceInfo, Signature, StaticField, VarSlot, Variable};
+
+// Bookkeeping for the innermost loops (and switches), used to resolve break/continue
+struct LoopContext {
+    label: Option<String>,
+    start: usize,            // Bytecode index 'continue' jumps back to
+    break_jumps: Vec<usize>, // Pending 'break' placeholders, patched at loop exit
+    is_switch: bool,         // Only 'break' applies; 'continue' goes to the enclosing loop
+    try_depth: usize,        // Enclosing tries; a jump out runs the finally blocks of the deeper ones
+}
+
+// An enclosing try statement of the function being emitted
+struct TryContext {
+    finally: Option<Vec<Stmt>>,
+    gaps: Vec<(usize, usize)>, // Finally copies emitted for early exits, left out of the protected range
+    caught: Option<VarSlot>,   // Exception of the catch clause being emitted, for a bare 'throw'
+}
+
+// One row of a function's exception table
+pub struct Handler {
+    pub start: u32,  // Protected range [start, end)
+    pub end: u32,
+    pub target: u32, // Handler address
+    pub class: i32,  // Class ID caught (subclasses included), -1 catches anything
+}
+
+// Exception table of one function, written to the .amc exception section
+pub struct ExceptionTable {
+    pub start: u32,             // Function's bytecode range (all code, for the top level)
+    pub end: u32,
+    pub frame_size: u32,        // A handler starts with the stack cut back to FP + frame_size
+    pub handlers: Vec<Handler>, // Inner tries first
+}
+
+// Runtime method table of a class, written to the .amc class section
+pub struct ClassEntry {
+    pub name: usize,               // Constant pool index of the class name (the runtime Class ID)
+    pub parent: Option<usize>,     // Class ID of the parent class
+    pub methods: Vec<(usize, u32)>, // (Method name constant, Address), inherited methods included
+}
 
 pub struct Emitter {
     pub code: Vec<u8>,
     pub constants: Vec<String>,
     pub calls_to_patch: Vec<(usize, String)>, // (Bytecode Index, Function Name)
+    pub classes: Vec<String>,        // Class names in declaration order (parents first)
+    pub class_table: Vec<ClassEntry>, // Built by finalize()
+    pub exception_tables: Vec<ExceptionTable>,
+    loops: Vec<LoopContext>,
+    pending_label: Option<String>, // Set by Stmt::Labeled for the loop that follows
+    current_function: Option<String>, // Function whose body is being emitted (None at top level)
+    current_class: Option<String>,    // Class whose methods are being emitted
+    tries: Vec<TryContext>,
+    handlers: Vec<Handler>, // Exception table rows of the current function
+    match_depth: usize,     // Block arms of match expressions being emitted
+    lambda_count: usize,    // Lambdas are emitted as functions named $lambda0, $lambda1, ...
+    captured: Vec<String>,  // Captured names of the lambda being emitted
+    type_params: Vec<String>, // Type parameters in scope (generic class members, generic functions)
+    narrowed: Vec<(String, Type)>, // Variables known to have a narrower type here: non-null after a null check
+                                   // or a non-null assignment, a subclass after an 'is' test
+    pub scope: ModuleScope, // Top-level names of the module being emitted and of its imports
+    pending_statics: Vec<String>, // Static fields of the class being declared whose initializers have not run yet
 }
 
 impl Emitter {
-    pub fn new() -> Self { Self { code: Vec::new(), constants: Vec::new(), calls_to_patch: Vec::new() } }
+    pub fn new() -> Self {
+        Self {
+            code: Vec::new(),
+            constants: Vec::new(),
+            calls_to_patch: Vec::new(),
+            classes: Vec::new(),
+            class_table: Vec::new(),
+            exception_tables: Vec::new(),
+            loops: Vec::new(),
+            pending_label: None,
+            current_function: None,
+            current_class: None,
+            tries: Vec::new(),
+            handlers: Vec::new(),
+            match_depth: 0,
+            lambda_count: 0,
+            captured: Vec::new(),
+            type_params: Vec::new(),
+            narrowed: Vec::new(),
+            scope: ModuleScope::default(),
+            pending_statics: Vec::new(),
+        }
+    }
 
     pub fn emit_byte(&mut self, b: u8) { self.code.push(b); }
     pub fn emit_int(&mut self, val: i32) {
         self.code.extend_from_slice(&val.to_le_bytes());
     }
 
-    pub fn emit_expr(&mut self, expr: &Expr, symbols: &mut SymbolTable) {
+    // Emits the expression and returns its static type
+    pub fn emit_expr(&mut self, expr: &Expr, symbols: &mut SymbolTable) -> Type {
         match expr {
             Expr::Integer(val) => {
                 self.emit_byte(OpCode::Push.into());
                 self.emit_int(*val);
+                Type::Int
             }
             Expr::StringLiteral(s) => {
                 // Deduplicate or just push
@@ -35,347 +114,2644 @@ impl Emitter {
                     self.constants.push(s.clone());
                     self.constants.len() - 1
                 };
-                
+
                 self.emit_byte(OpCode::LoadConst.into());
                 self.emit_int(index as i32);
+                Type::Str
+            }
+            Expr::Null => {
+                // 0 is never a string index or heap handle
+                self.emit_byte(OpCode::Push.into());
+                self.emit_int(0);
+                Type::Null
             }
-            Expr::NewArray(size) => {
-                self.emit_expr(size, symbols);
+            Expr::NewArray(elem, size) => {
+                self.check_type_exists(elem, symbols);
+                let size_ty = self.emit_expr(size, symbols);
+                self.expect_type(symbols, &size_ty, &Type::Int, "array size");
                 self.emit_byte(OpCode::NewArray.into());
+                Type::Array(Box::new(elem.clone()))
             }
-            Expr::NewInstance(class_name) => {
+            Expr::NewInstance(class_name, type_args, args) => {
                 // 1. Find the class
+                if symbols.interfaces.contains_key(class_name) {
+                    panic!("Cannot instantiate interface '{}'", class_name);
+                }
+                if symbols.enums.contains_key(class_name) {
+                    panic!("Cannot instantiate enum '{}'", class_name);
+                }
                 let class_info = symbols.classes.get(class_name)
-                    .expect(&format!("Undefined class: {}", class_name));
-                
+                    .unwrap_or_else(|| panic!("Undefined class: {}", class_name));
+                let constructor = class_info.constructor;
+                let field_count = class_info.fields.len();
+                let type_params = class_info.type_params.clone();
+                if type_params.is_empty() && !type_args.is_empty() {
+                    panic!("Class '{}' is not generic and takes no type arguments", class_name);
+                }
+                if !type_args.is_empty() {
+                    self.check_type_exists(&Type::Generic(class_name.clone(), type_args.clone()), symbols);
+                }
+                // Explicit type arguments, or bindings inferred from the constructor arguments
+                let mut bindings: Vec<(String, Type)> = type_params.iter().cloned().zip(type_args.iter().cloned()).collect();
+
                 // 2. Emit OP_NEW_INSTANCE
                 self.emit_byte(OpCode::NewInstance.into());
-                
+
                 // 3. Emit Class ID (Hash of name for now, or just 0 placeholder) and Field Count
                 // For simplicity in v0.3, we pass Field Count directly so VM knows how much to alloc.
                 // We can use the constant pool index of the class name as the ID.
                 let name_idx = self.add_constant(class_name.clone());
                 self.emit_int(name_idx as i32);
-                self.emit_int(class_info.fields.len() as i32);
+                self.emit_int(field_count as i32);
+
+                // 4. Run the constructor on a copy of the reference, leaving the instance on the stack
+                match constructor {
+                    Some(_) => {
+                        let constructor_name = format!("{}_{}", class_name, class_name);
+                        let visibility = symbols.functions[&constructor_name].visibility;
+                        self.check_access(class_name, visibility, &format!("constructor of '{}'", class_name), symbols);
+                        let params = symbols.functions[&constructor_name].signature.params[1..].to_vec();
+                        let callee = format!("constructor of '{}'", class_name);
+                        let args = self.resolve_args(args, symbols.functions.get(&constructor_name).cloned(), 1, &callee, symbols);
+                        self.emit_byte(OpCode::Dup.into());
+                        bindings = self.emit_generic_args(&args, &params, &type_params, bindings, &callee, symbols);
+                        self.emit_byte(OpCode::Call.into());
+                        self.calls_to_patch.push((self.code.len(), constructor_name));
+                        self.emit_int(0);
+                        self.emit_byte((args.len() + 1) as u8); // +1 for 'this'
+                        self.forget_shared(symbols);
+                        self.emit_byte(OpCode::Pop.into()); // Discard the constructor's return value
+                    }
+                    None if !args.is_empty() => panic!("Class '{}' has no constructor taking arguments", class_name),
+                    None => bindings = self.emit_generic_args(&[], &[], &type_params, bindings, &format!("'new {}'", class_name), symbols),
+                }
+                if type_params.is_empty() {
+                    Type::Named(class_name.clone())
+                } else {
+                    Type::Generic(class_name.clone(), bindings.into_iter().map(|(_, ty)| ty).collect())
+                }
             }
             Expr::GetField(obj_expr, field_name) => {
-                self.emit_expr(obj_expr, symbols); // Push object ref
-                
-                // Hack: Find field index by looking at all classes (since we don't track types yet)
-                let mut field_idx = None;
-                
-                // Sort classes to ensure deterministic compilation
-                let mut classes: Vec<_> = symbols.classes.values().collect();
-                classes.sort_by_key(|c| &c.name);
-
-                for cls in classes {
-                    if let Some(idx) = cls.fields.get(field_name) {
-                        field_idx = Some(*idx);
-                        break;
+                if let Some(class_name) = self.static_receiver(obj_expr, symbols) {
+                    if let Some(info) = symbols.enums.get(&class_name).cloned() {
+                        return self.emit_variant(&info, field_name, &[], symbols);
                     }
+                    // Class.field reads the class-scoped global
+                    let var = self.resolve_static_field(&class_name, field_name, symbols).var;
+                    return self.emit_load(var, symbols);
+                }
+                // geometry.origin reads a global of an imported module
+                if let Some(name) = self.module_member(obj_expr, field_name, symbols) {
+                    return self.emit_expr(&Expr::Variable(name), symbols);
                 }
-                let idx = field_idx.expect(&format!("Field '{}' not found in any known class", field_name));
-                
+                let obj_ty = self.emit_expr(obj_expr, symbols); // Push object ref
+                if matches!(obj_ty, Type::Array(_)) && field_name == "length" {
+                    self.emit_byte(OpCode::ArrayLength.into());
+                    return Type::Int;
+                }
+                let field = self.resolve_field(&obj_ty, field_name, symbols);
+
                 self.emit_byte(OpCode::GetField.into());
-                self.emit_int(idx as i32);
+                self.emit_int(field.index as i32);
+                field.ty
             }
             Expr::MethodCall(obj, method_name, args) => {
-                self.emit_expr(obj, symbols); // 1. Push Object (this)
-                for arg in args {
-                    self.emit_expr(arg, symbols); // 2. Push Args
+                // geometry.area(...) calls a function of an imported module
+                if let Some(name) = self.module_member(obj, method_name, symbols) {
+                    return self.emit_expr(&Expr::Call(name, args.clone()), symbols);
+                }
+                if let Some(class_name) = self.static_receiver(obj, symbols) {
+                    if let Some(info) = symbols.enums.get(&class_name).cloned() {
+                        if info.variants.iter().any(|v| v.name == *method_name) {
+                            return self.emit_variant(&info, method_name, args, symbols); // Shape.Circle(5)
+                        }
+                        // Color.from(n) converts an int without checking it names a variant
+                        if method_name != "from" || info.has_payloads() {
+                            panic!("Enum '{}' has no static method '{}'", class_name, method_name);
+                        }
+                        self.emit_args(args, &[Type::Int], &format!("'{}.from'", class_name), symbols);
+                        return Type::Named(class_name);
+                    }
+                    let owner = symbols.find_static_method_owner(&class_name, method_name)
+                        .unwrap_or_else(|| panic!("Class '{}' has no static method '{}'", class_name, method_name))
+                        .name.clone();
+                    return self.emit_static_call(&owner, method_name, args, symbols);
                 }
+                let obj_ty = self.emit_expr(obj, symbols); // 1. Push Object (this)
+                self.emit_invoke(&obj_ty, method_name, args, symbols)
+            }
+            Expr::SafeGetField(..) | Expr::SafeMethodCall(..) => self.emit_safe_access(expr, &mut None, symbols),
+            Expr::Coalesce(value, default) => {
+                // value ?? default: the default is only evaluated when the value is null.
+                // A '?.' chain on the left jumps straight to the default, so it may end in an int member.
+                let mut to_default = matches!(**value, Expr::SafeGetField(..) | Expr::SafeMethodCall(..)).then(Vec::new);
+                let value_ty = match to_default {
+                    Some(_) => self.emit_safe_access(value, &mut to_default, symbols),
+                    None => self.emit_expr(value, symbols),
+                };
+                let end_jump = if value_ty.is_nullable() {
+                    self.emit_byte(OpCode::Dup.into());
+                    self.emit_byte(OpCode::Push.into());
+                    self.emit_int(0);
+                    self.emit_byte(OpCode::Equal.into());
+                    let end_jump = self.emit_jump(OpCode::JumpIfFalse.into());
+                    self.emit_byte(OpCode::Pop.into());
+                    end_jump
+                } else if to_default.is_some() {
+                    self.emit_jump(OpCode::Jump.into())
+                } else {
+                    panic!("Left side of '??' has type '{}', which is never null", value_ty);
+                };
+                for jump in to_default.unwrap_or_default() {
+                    self.patch_jump(jump);
+                }
+                let default_ty = self.emit_expr(default, symbols);
+                self.patch_jump(end_jump);
+                let base = value_ty.non_null();
+                match () {
+                    _ if value_ty == Type::Null => default_ty,
+                    _ if symbols.is_assignable(&default_ty, &base) => if default_ty.is_nullable() { base.nullable() } else { base },
+                    _ if symbols.is_assignable(&base, &default_ty) => default_ty,
+                    _ => panic!("Type mismatch in '??': '{}' and '{}' have no common type", value_ty, default_ty),
+                }
+            }
+            Expr::Conditional(cond, then_value, else_value) => {
+                // Same shape as Stmt::If, with each branch leaving its value on the stack
+                let cond_ty = self.emit_expr(cond, symbols);
+                self.expect_type(symbols, &cond_ty, &Type::Int, "condition of '?:'");
+                let before = self.narrowed.clone();
 
-                // Find which class has this method
-                let mut found_class = None;
-                // Sort classes to ensure deterministic compilation in case of name collisions
-                let mut classes: Vec<_> = symbols.classes.values().collect();
-                classes.sort_by_key(|c| &c.name);
+                let else_jump = self.emit_jump(OpCode::JumpIfFalse.into());
+                self.narrow_by_condition(cond, true, symbols);
+                let then_ty = self.emit_expr(then_value, symbols);
+                self.narrowed = before.clone();
+                let end_jump = self.emit_jump(OpCode::Jump.into());
 
-                for cls in classes {
-                    if cls.methods.contains(method_name) {
-                        found_class = Some(cls.name.clone());
-                        break;
-                    }
+                self.patch_jump(else_jump);
+                self.narrow_by_condition(cond, false, symbols);
+                let else_ty = self.emit_expr(else_value, symbols);
+                self.narrowed = before;
+                self.patch_jump(end_jump);
+
+                if then_ty == Type::Void || else_ty == Type::Void {
+                    panic!("Both branches of '?:' need a value");
+                }
+                // Both branches must agree: one type fits the other, or one is null and the other can be
+                match () {
+                    _ if symbols.is_assignable(&else_ty, &then_ty) => then_ty,
+                    _ if symbols.is_assignable(&then_ty, &else_ty) => else_ty,
+                    _ if then_ty == Type::Null && self.can_be_null(&else_ty, symbols) => else_ty.nullable(),
+                    _ if else_ty == Type::Null && self.can_be_null(&then_ty, symbols) => then_ty.nullable(),
+                    _ => panic!("Branches of '?:' have different types: '{}' and '{}'", then_ty, else_ty),
+                }
+            }
+            Expr::Tuple(elems) => {
+                // A fixed-size array holding the elements
+                self.emit_byte(OpCode::Push.into());
+                self.emit_int(elems.len() as i32);
+                self.emit_byte(OpCode::NewArray.into());
+                let mut types = Vec::new();
+                for (i, elem) in elems.iter().enumerate() {
+                    self.emit_byte(OpCode::Dup.into());
+                    self.emit_byte(OpCode::Push.into());
+                    self.emit_int(i as i32);
+                    let ty = self.emit_expr(elem, symbols);
+                    if ty == Type::Void { panic!("Tuple element {} has no value", i); }
+                    self.emit_byte(OpCode::StoreArray.into());
+                    types.push(ty);
+                }
+                Type::Tuple(types)
+            }
+            Expr::TupleElement(tuple, index) => {
+                let tuple_ty = self.emit_expr(tuple, symbols);
+                let elem_ty = Self::tuple_element(&tuple_ty, *index);
+                self.emit_byte(OpCode::Push.into());
+                self.emit_int(*index as i32);
+                self.emit_byte(OpCode::LoadArray.into());
+                elem_ty
+            }
+            Expr::ValueEquals(left, right) => {
+                let ty = self.emit_expr(left, symbols);
+                self.emit_expr(right, symbols);
+                self.emit_value_equals(&ty, symbols);
+                Type::Int
+            }
+            Expr::ValueHash(values) => {
+                self.emit_byte(OpCode::Push.into());
+                self.emit_int(17);
+                for value in values {
+                    let ty = self.emit_expr(value, symbols);
+                    self.emit_value_hash(&ty, symbols);
+                    self.emit_byte(OpCode::HashCombine.into());
                 }
-                let class_name = found_class.expect(&format!("Method '{}' not found in any known class", method_name));
-                let full_name = format!("{}_{}", class_name, method_name);
+                Type::Int
+            }
+            Expr::ValueText(value) => {
+                let ty = self.emit_expr(value, symbols);
+                self.emit_value_text(&ty, symbols);
+                Type::Str
+            }
+            Expr::InstanceOf(value, target) => {
+                let value_ty = self.emit_expr(value, symbols);
+                let class_idx = self.checked_class(&value_ty, target, "is", symbols);
+                self.emit_byte(OpCode::InstanceOf.into());
+                self.emit_int(class_idx);
+                Type::Int
+            }
+            Expr::Cast(value, target) => {
+                let value_ty = self.emit_expr(value, symbols);
+                let class_idx = self.checked_class(&value_ty, &target.non_null(), "as", symbols);
+                // 'as Circle?' lets null through, 'as Circle' fails on it
+                let null_jump = target.is_nullable().then(|| self.emit_null_guard());
+                self.emit_byte(OpCode::CheckCast.into());
+                self.emit_int(class_idx);
+                if let Some(jump) = null_jump { self.patch_jump(jump); }
+                target.clone()
+            }
+            Expr::SuperCall(None, _) => {
+                panic!("super(...) can only be the first statement of a constructor");
+            }
+            Expr::NamedArg(name, _) => {
+                panic!("Named argument '{}' can only be passed to a function, method or constructor", name);
+            }
+            Expr::VarArgs(_) => unreachable!("packed arguments are emitted by emit_generic_args"),
+            Expr::SuperCall(Some(method_name), args) => {
+                // Statically bound: skip the current class and call the nearest parent implementation
+                let class_name = self.current_class.clone().expect("'super' used outside of a class");
+                let parent = symbols.classes[&class_name].parent.clone()
+                    .unwrap_or_else(|| panic!("Class '{}' has no parent class for 'super'", class_name));
+                let owner = symbols.find_method_owner(&parent, method_name)
+                    .unwrap_or_else(|| panic!("Method '{}' not found in parents of '{}'", method_name, class_name))
+                    .name.clone();
+                let full_name = format!("{}_{}", owner, method_name);
+                let sig = symbols.functions[&full_name].signature.clone();
+                let visibility = symbols.functions[&full_name].visibility;
+                self.check_access(&owner, visibility, &format!("method '{}.{}'", owner, method_name), symbols);
 
+                let callee = format!("method '{}.{}'", owner, method_name);
+                let args = self.resolve_args(args, symbols.functions.get(&full_name).cloned(), 1, &callee, symbols);
+                self.emit_load_var("this", symbols);
+                self.emit_args(&args, &sig.params[1..], &callee, symbols);
                 self.emit_byte(OpCode::Call.into());
                 self.calls_to_patch.push((self.code.len(), full_name));
                 self.emit_int(0);
                 self.emit_byte((args.len() + 1) as u8); // +1 for 'this'
+                self.forget_shared(symbols);
+                sig.return_type
             }
             Expr::ArrayAccess(name, index) => {
-                // Load array ref
-                if let Some(idx) = symbols.locals.get(name) {
-                    self.emit_byte(OpCode::LoadLocal.into());
-                    self.emit_int(*idx as i32);
-                } else {
-                    let idx = symbols.variables.get(name).expect("Undefined variable");
-                    self.emit_byte(OpCode::LoadGlobal.into());
-                    self.emit_int(*idx as i32);
+                let array_ty = self.emit_load_var(name, symbols); // Load array ref
+                if symbols.method_signature(&array_ty.non_null(), "operator[]").is_some() {
+                    return self.emit_invoke(&array_ty, "operator[]", std::slice::from_ref(&**index), symbols);
                 }
-                self.emit_expr(index, symbols); // Load index
+                let Type::Array(elem) = array_ty else { panic!("Cannot index '{}' of type '{}'", name, array_ty) };
+                let index_ty = self.emit_expr(index, symbols); // Load index
+                self.expect_type(symbols, &index_ty, &Type::Int, "array index");
                 self.emit_byte(OpCode::LoadArray.into());
+                *elem
             }
             Expr::Variable(name) => {
-                if let Some(index) = symbols.locals.get(name) {
-                    self.emit_byte(OpCode::LoadLocal.into());
-                    self.emit_int(*index as i32);
-                } else {
-                    let index = symbols.variables.get(name)
-                        .expect(&format!("Undefined variable: {}", name));
-                    self.emit_byte(OpCode::LoadGlobal.into());
-                    self.emit_int(*index as i32);
+                if self.lookup_var(name, symbols).is_none() {
+                    let function = self.scope.resolve(name).unwrap_or(name).to_string();
+                    if let Some(info) = symbols.functions.get(&function) {
+                        if !info.type_params.is_empty() {
+                            panic!("Generic function '{}' cannot be used as a value", name);
+                        }
+                        // A named function used as a value: a closure without captures
+                        let ty = Type::Function(info.signature.params.clone(), Box::new(info.signature.return_type.clone()));
+                        self.emit_byte(OpCode::MakeClosure.into());
+                        self.calls_to_patch.push((self.code.len(), function));
+                        self.emit_int(0);
+                        self.emit_byte(0);
+                        return ty;
+                    }
                 }
+                self.emit_load_var(name, symbols)
             }
             Expr::Call(name, args) => {
-                for arg in args {
-                    self.emit_expr(arg, symbols);
+                // A variable holding a function shadows functions of the same name
+                if let Some(var) = self.lookup_var(name, symbols) {
+                    let ty = self.emit_load(var, symbols);
+                    let ty = self.narrowed_type(name, ty);
+                    return self.emit_indirect_call(&ty, args, &format!("'{}'", name), symbols);
                 }
+                // Inside a class, a bare name may refer to one of its static methods
+                let owner = self.current_class.as_ref()
+                    .and_then(|c| symbols.find_static_method_owner(c, name))
+                    .map(|cls| cls.name.clone());
+                if let Some(owner) = owner {
+                    return self.emit_static_call(&owner, name, args, symbols);
+                }
+                let name = &self.scope.resolve(name).unwrap_or(name).to_string();
+                let info = symbols.functions.get(name)
+                    .unwrap_or_else(|| panic!("Undefined function: {}", name));
+                let (sig, type_params) = (info.signature.clone(), info.type_params.clone());
+                let callee = format!("function '{}'", name);
+                let args = self.resolve_args(args, Some(info.clone()), 0, &callee, symbols);
+                // Generic functions bind their type parameters from the arguments
+                let bindings = self.emit_generic_args(&args, &sig.params, &type_params, Vec::new(), &callee, symbols);
                 self.emit_byte(OpCode::Call.into());
-                
+
                 // Emit placeholder address and record for patching
                 self.calls_to_patch.push((self.code.len(), name.clone()));
-                self.emit_int(0); 
+                self.emit_int(0);
                 self.emit_byte(args.len() as u8);
+                self.forget_shared(symbols);
+                sig.return_type.substitute(&bindings)
+            }
+            Expr::Match(scrutinee, arms) => self.emit_match(scrutinee, arms, symbols),
+            Expr::Lambda(params, body) => self.emit_lambda(params, body, symbols),
+            Expr::CallValue(callee, args) => {
+                let ty = self.emit_expr(callee, symbols);
+                self.emit_indirect_call(&ty, args, "function value", symbols)
             }
             Expr::Binary(left, op, right) => {
-                self.emit_expr(left, symbols);
-                self.emit_expr(right, symbols);
+                let left_ty = self.emit_expr(left, symbols);
+                if let Some(method) = self.operator_method(&left_ty, op, right, symbols) {
+                    // a + b calls a.operator+(b); a != b is (a.operator==(b)) == 0
+                    if matches!(op, Op::Equal | Op::NotEqual) {
+                        self.emit_operator_equals(&left_ty, right, symbols);
+                        if matches!(op, Op::NotEqual) {
+                            self.emit_byte(OpCode::Push.into());
+                            self.emit_int(0);
+                            self.emit_byte(OpCode::Equal.into());
+                        }
+                        return Type::Int;
+                    }
+                    if left_ty.is_nullable() {
+                        panic!("Value of type '{}' may be null: check it with '!= null' before using {:?}", left_ty, op);
+                    }
+                    let result_ty = self.emit_invoke(&left_ty, &method, std::slice::from_ref(&**right), symbols);
+                    if matches!(op, Op::NotEqual) {
+                        self.emit_byte(OpCode::Push.into());
+                        self.emit_int(0);
+                        self.emit_byte(OpCode::Equal.into());
+                    }
+                    return result_ty;
+                }
+                let right_ty = self.emit_expr(right, symbols);
                 match op {
                     Op::Add => self.emit_byte(OpCode::Add.into()),
                     Op::Sub => self.emit_byte(OpCode::Sub.into()),
                     Op::Mul => self.emit_byte(OpCode::Mul.into()),
                     Op::Div => self.emit_byte(OpCode::Div.into()),
                     Op::LessThan => self.emit_byte(OpCode::Less.into()),
+                    Op::Equal => self.emit_byte(OpCode::Equal.into()),
+                    Op::NotEqual => {
+                        // a != b  =>  (a == b) == 0
+                        self.emit_byte(OpCode::Equal.into());
+                        self.emit_byte(OpCode::Push.into());
+                        self.emit_int(0);
+                        self.emit_byte(OpCode::Equal.into());
+                    }
+                }
+                // '+' also concatenates two strings; == and != compare any two values of related types;
+                // everything else is integer arithmetic
+                if matches!(op, Op::Equal | Op::NotEqual) && self.is_data_enum(&left_ty, symbols) {
+                    panic!("Enum '{}' carries data and cannot be compared with {:?}; use match", left_ty, op);
+                }
+                if matches!(op, Op::Equal | Op::NotEqual) && [&left_ty, &right_ty].iter().any(|t| matches!(t, Type::Tuple(_))) {
+                    panic!("Tuples cannot be compared with {:?}; compare their elements", op);
+                }
+                // T erases to a plain slot: nothing at runtime tells a record (compared by its fields) from a reference
+                let param = [&left_ty, &right_ty].into_iter().find(|t| matches!(t, Type::Param(_)));
+                if let (Some(param), Op::Equal | Op::NotEqual) = (param, op) {
+                    panic!("Values of type parameter '{}' cannot be compared with {:?}; pass a comparison function instead", param, op);
+                }
+                let comparable = left_ty != Type::Void
+                    && (symbols.is_assignable(&left_ty, &right_ty) || symbols.is_assignable(&right_ty, &left_ty)
+                        || left_ty == Type::Null && self.can_be_null(&right_ty, symbols)
+                        || right_ty == Type::Null && self.can_be_null(&left_ty, symbols));
+                match (op, &left_ty, &right_ty) {
+                    (Op::Add, Type::Str, Type::Str) => Type::Str,
+                    (Op::Equal | Op::NotEqual, _, _) if comparable => Type::Int,
+                    (_, Type::Int, Type::Int) => Type::Int,
+                    _ => panic!("Operator {:?} cannot be applied to '{}' and '{}'", op, left_ty, right_ty),
                 }
             }
         }
     }
 
-    // Emits a jump instruction with a placeholder offset. Returns the index of the placeholder.
-    fn emit_jump(&mut self, instruction: u8) -> usize {
-        self.emit_byte(instruction);
-        self.emit_byte(0xFF); // Placeholder (4 bytes)
-        self.emit_byte(0xFF);
-        self.emit_byte(0xFF);
-        self.emit_byte(0xFF);
-        self.code.len() - 4
+    // The 'operator' method a binary operator calls on a class-typed left operand, if its class defines one.
+    // == and != against null keep comparing references.
+    fn operator_method(&self, left_ty: &Type, op: &Op, right: &Expr, symbols: &SymbolTable) -> Option<String> {
+        let symbol = match op {
+            Op::Add => "+",
+            Op::Sub => "-",
+            Op::Mul => "*",
+            Op::Div => "/",
+            Op::LessThan => "<",
+            Op::Equal | Op::NotEqual if matches!(right, Expr::Null) => return None,
+            Op::Equal | Op::NotEqual => "==",
+        };
+        let method = format!("operator{}", symbol);
+        symbols.method_signature(&left_ty.non_null(), &method).map(|_| method)
     }
 
-    fn patch_jump(&mut self, offset_index: usize) {
-        let jump_dist = (self.code.len() - offset_index - 4) as i32;
-        let bytes = jump_dist.to_le_bytes();
-        for i in 0..4 {
-            self.code[offset_index + i] = bytes[i];
+    // a == b with the left operand pushed: a.operator==(b) when both are non-null, otherwise they are
+    // equal only if both are null
+    fn emit_operator_equals(&mut self, left_ty: &Type, right: &Expr, symbols: &mut SymbolTable) {
+        let owner = left_ty.non_null().class_name().and_then(|c| symbols.find_method_owner(c, "operator=="));
+        if let Some(owner) = owner {
+            let visibility = symbols.functions[&format!("{}_operator==", owner.name)].visibility;
+            self.check_access(&owner.name, visibility, &format!("method '{}.operator=='", owner.name), symbols);
+        }
+        let sig = symbols.method_signature(&left_ty.non_null(), "operator==").unwrap();
+        let right_ty = self.emit_expr(right, symbols);
+        self.expect_type(symbols, &right_ty.non_null(), &sig.params[0], &format!("argument 1 of method '{}.operator=='", left_ty.non_null()));
+        if !left_ty.is_nullable() && !right_ty.is_nullable() {
+            self.emit_invoke_on_stack("operator==", 1, symbols);
+            return;
+        }
+
+        symbols.push_scope();
+        let b = self.emit_temp("$right", &right_ty, symbols);
+        let a = self.emit_temp("$left", left_ty, symbols);
+        for slot in [&a, &b] {
+            self.emit_load_slot(slot);
+            self.emit_byte(OpCode::Push.into());
+            self.emit_int(0);
+            self.emit_byte(OpCode::Equal.into());
         }
+        self.emit_byte(OpCode::Add.into()); // Number of null operands
+        let both_set = self.emit_jump(OpCode::JumpIfFalse.into());
+        self.emit_load_slot(&a);
+        self.emit_load_slot(&b);
+        self.emit_byte(OpCode::Equal.into());
+        let end = self.emit_jump(OpCode::Jump.into());
+        self.patch_jump(both_set);
+        self.emit_load_slot(&a);
+        self.emit_load_slot(&b);
+        self.emit_invoke_on_stack("operator==", 1, symbols);
+        self.patch_jump(end);
+        symbols.pop_scope();
     }
 
-    fn add_constant(&mut self, s: String) -> usize {
-        if let Some(idx) = self.constants.iter().position(|c| *c == s) {
-            idx
-        } else {
-            self.constants.push(s);
-            self.constants.len() - 1
+    // Calls a method on the object just pushed, dispatching on its runtime class
+    fn emit_invoke(&mut self, obj_ty: &Type, method_name: &str, args: &[Expr], symbols: &mut SymbolTable) -> Type {
+        if obj_ty.is_nullable() {
+            Self::null_access(obj_ty, method_name);
+        }
+        if matches!(obj_ty, Type::Named(name) if symbols.enums.contains_key(name)) {
+            // c.value() emits nothing: the enum already is its integer value
+            if method_name != "value" || self.is_data_enum(obj_ty, symbols) {
+                panic!("Enum '{}' has no method '{}'", obj_ty, method_name);
+            }
+            self.emit_args(args, &[], &format!("'{}.value'", obj_ty), symbols);
+            return Type::Int;
+        }
+
+        // obj.callback(x) calls a function stored in a field, if no method has that name
+        let field_call = match obj_ty.class_name() {
+            Some(name) => symbols.method_signature(obj_ty, method_name).is_none()
+                && symbols.classes.get(name).is_some_and(|c| c.fields.contains_key(method_name)),
+            _ => false,
+        };
+        if field_call {
+            let field = self.resolve_field(obj_ty, method_name, symbols);
+            self.emit_byte(OpCode::GetField.into());
+            self.emit_int(field.index as i32);
+            return self.emit_indirect_call(&field.ty, args, &format!("field '{}.{}'", obj_ty, method_name), symbols);
+        }
+
+        // Resolved against the static type (a class or an interface);
+        // the receiver's runtime class picks the implementation
+        let sig = symbols.method_signature(obj_ty, method_name)
+            .unwrap_or_else(|| panic!("Type '{}' has no method '{}'", obj_ty, method_name));
+        let mut declared = None;
+        if let Some(class_name) = obj_ty.class_name() {
+            if let Some(owner) = symbols.find_method_owner(class_name, method_name) {
+                let full_name = format!("{}_{}", owner.name, method_name);
+                let visibility = symbols.functions[&full_name].visibility;
+                self.check_access(&owner.name, visibility, &format!("method '{}.{}'", owner.name, method_name), symbols);
+                declared = Some(full_name);
+            }
+        }
+        let callee = format!("method '{}.{}'", obj_ty, method_name);
+        let info = declared.as_ref().and_then(|name| symbols.functions.get(name)).cloned();
+        let is_private = info.as_ref().is_some_and(|info| info.visibility == Visibility::Private);
+        let args = self.resolve_args(args, info, 1, &callee, symbols);
+        self.emit_args(&args, &sig.params, &callee, symbols); // 2. Push Args
+
+        if is_private {
+            // Private methods are not virtual: call the declaring class's own
+            self.emit_byte(OpCode::Call.into());
+            self.calls_to_patch.push((self.code.len(), declared.unwrap()));
+            self.emit_int(0);
+            self.emit_byte((args.len() + 1) as u8); // +1 for 'this'
+            self.forget_shared(symbols);
+            return sig.return_type;
         }
+        self.emit_byte(OpCode::Invoke.into());
+        let name_idx = self.add_constant(method_name.to_string());
+        self.emit_int(name_idx as i32);
+        self.emit_byte((args.len() + 1) as u8); // +1 for 'this'
+        self.forget_shared(symbols);
+        sig.return_type
     }
 
-    pub fn finalize(&mut self, symbols: &SymbolTable) {
-        for (index, name) in &self.calls_to_patch {
-            let func_info = symbols.functions.get(name)
-                .expect(&format!("Undefined function: {}", name));
-            
-            let bytes = (func_info.address as i32).to_le_bytes();
-            for i in 0..4 {
-                self.code[index + i] = bytes[i];
+    // Leaves the object pushed before it for the access that follows, or skips to the returned jump with
+    // the null still on the stack as the result
+    fn emit_null_guard(&mut self) -> usize {
+        self.emit_byte(OpCode::Dup.into());
+        self.emit_byte(OpCode::Push.into());
+        self.emit_int(0);
+        self.emit_byte(OpCode::Equal.into());
+        let not_null = self.emit_jump(OpCode::JumpIfFalse.into());
+        let end_jump = self.emit_jump(OpCode::Jump.into());
+        self.patch_jump(not_null);
+        end_jump
+    }
+
+    // obj?.field / obj?.method(args): skipped when obj is null. The result is then null, or, inside the
+    // left side of '??', a jump to the default (collected in to_default), which also works for int members.
+    fn emit_safe_access(&mut self, expr: &Expr, to_default: &mut Option<Vec<usize>>, symbols: &mut SymbolTable) -> Type {
+        let (Expr::SafeGetField(obj, member) | Expr::SafeMethodCall(obj, member, _)) = expr else { unreachable!() };
+        // a?.b?.c: a null anywhere in the chain ends it
+        let obj_ty = match **obj {
+            Expr::SafeGetField(..) | Expr::SafeMethodCall(..) => self.emit_safe_access(obj, to_default, symbols),
+            _ => self.emit_expr(obj, symbols),
+        };
+        let end_jump = match to_default {
+            Some(exits) => {
+                self.emit_byte(OpCode::Dup.into());
+                self.emit_byte(OpCode::Push.into());
+                self.emit_int(0);
+                self.emit_byte(OpCode::Equal.into());
+                let not_null = self.emit_jump(OpCode::JumpIfFalse.into());
+                self.emit_byte(OpCode::Pop.into());
+                exits.push(self.emit_jump(OpCode::Jump.into()));
+                self.patch_jump(not_null);
+                None
             }
+            None => Some(self.emit_null_guard()),
+        };
+        let ty = match expr {
+            Expr::SafeMethodCall(_, _, args) => self.emit_invoke(&obj_ty.non_null(), member, args, symbols),
+            _ => {
+                let field = self.resolve_field(&obj_ty.non_null(), member, symbols);
+                self.emit_byte(OpCode::GetField.into());
+                self.emit_int(field.index as i32);
+                field.ty
+            }
+        };
+        let Some(end_jump) = end_jump else { return ty };
+        self.patch_jump(end_jump);
+        match ty {
+            Type::Void => Type::Void,
+            ty if self.can_be_null(&ty, symbols) => ty.nullable(),
+            ty => panic!("'?.{}' has type '{}', which cannot be null: give a default with '??'", member, ty),
         }
     }
 
-    pub fn emit_stmt(&mut self, stmt: &Stmt, symbols: &mut SymbolTable) {
-        match stmt {
-            Stmt::VarDecl(name, expr) => {
-                self.emit_expr(expr, symbols); // Push value
-                
-                // Assign index
-                let index = symbols.next_var_index;
-                symbols.variables.insert(name.clone(), index);
-                symbols.next_var_index += 1;
+    // Objects, strings, arrays and functions are references, which leaves 0 free for null.
+    // Ints and enums use every value, and a type parameter may stand for int.
+    fn can_be_null(&self, ty: &Type, symbols: &SymbolTable) -> bool {
+        match ty {
+            Type::Str | Type::Array(_) | Type::Tuple(_) | Type::Function(..) | Type::Generic(..) | Type::Nullable(_) | Type::Null => true,
+            Type::Named(name) => !symbols.enums.contains_key(name),
+            _ => false,
+        }
+    }
 
-                self.emit_byte(OpCode::StoreGlobal.into());
-                self.emit_int(index as i32);
+    // The class an 'is'/'as' tests at runtime, as its name constant. Only classes keep their identity at runtime,
+    // and the value's static type must leave room for one.
+    fn checked_class(&mut self, value_ty: &Type, target: &Type, op: &str, symbols: &SymbolTable) -> i32 {
+        let class_name = match target {
+            Type::Named(name) if symbols.classes.contains_key(name) => name,
+            Type::Named(name) if symbols.interfaces.contains_key(name) => {
+                panic!("Cannot use '{}' with interface '{}': only classes can be tested at runtime", op, name)
             }
-            Stmt::Assign(name, expr) => {
-                self.emit_expr(expr, symbols);
-                if let Some(index) = symbols.locals.get(name) {
-                    self.emit_byte(OpCode::StoreLocal.into());
-                    self.emit_int(*index as i32);
-                } else if let Some(index) = symbols.variables.get(name) {
-                    self.emit_byte(OpCode::StoreGlobal.into());
-                    self.emit_int(*index as i32);
-                } else {
-                    panic!("Undefined variable: {}", name);
-                }
+            Type::Named(name) if symbols.enums.contains_key(name) => panic!("Cannot use '{}' with enum '{}': use 'match' instead", op, name),
+            Type::Named(name) => panic!("Unknown class '{}'", name),
+            Type::Generic(..) | Type::Param(_) => {
+                panic!("Cannot use '{}' with '{}': type arguments are erased at runtime", op, target)
             }
-            Stmt::ArraySet(name, index, value) => {
-                // Load array ref
-                if let Some(idx) = symbols.locals.get(name) {
-                    self.emit_byte(OpCode::LoadLocal.into());
-                    self.emit_int(*idx as i32);
-                } else {
-                    let idx = symbols.variables.get(name).expect("Undefined variable");
-                    self.emit_byte(OpCode::LoadGlobal.into());
-                    self.emit_int(*idx as i32);
+            _ => unreachable!(),
+        };
+        let related = match value_ty.non_null().class_name() {
+            Some(name) if symbols.interfaces.contains_key(name) => true,
+            Some(_) => symbols.is_assignable(target, &value_ty.non_null()) || symbols.is_assignable(&value_ty.non_null(), target),
+            None => false,
+        };
+        if !related {
+            panic!("Cannot use '{}' on a value of type '{}': it can never be a '{}'", op, value_ty, class_name);
+        }
+        self.add_constant(class_name.clone()) as i32
+    }
+
+    fn tuple_element(tuple_ty: &Type, index: usize) -> Type {
+        match tuple_ty {
+            Type::Tuple(elems) if index < elems.len() => elems[index].clone(),
+            Type::Tuple(elems) => panic!("Tuple '{}' has no element {}: it has {}", tuple_ty, index, elems.len()),
+            Type::Nullable(_) => panic!("Value of type '{}' may be null: check it with '!= null' first", tuple_ty),
+            _ => panic!("Cannot take element {} of a value of type '{}'", index, tuple_ty),
+        }
+    }
+
+    // How records compare, hash and show their fields, by type. Ints, strings and simple enums use their value,
+    // objects their own equals/hashCode/toString if they have them, tuples their elements. Anything else
+    // compares and hashes by reference and shows as its type.
+
+    fn has_method(&self, ty: &Type, method: &str, params: &[Type], return_type: &Type, symbols: &SymbolTable) -> bool {
+        symbols.method_signature(ty, method).is_some_and(|sig| sig.params == params && sig.return_type == *return_type)
+    }
+
+    fn is_simple_enum(&self, ty: &Type, symbols: &SymbolTable) -> bool {
+        matches!(ty, Type::Named(name) if symbols.enums.contains_key(name)) && !self.is_data_enum(ty, symbols)
+    }
+
+    // Calls a method on the receiver (and arguments) already on the stack
+    fn emit_invoke_on_stack(&mut self, method: &str, arg_count: u8, symbols: &SymbolTable) {
+        self.emit_byte(OpCode::Invoke.into());
+        let name_idx = self.add_constant(method.to_string());
+        self.emit_int(name_idx as i32);
+        self.emit_byte(arg_count + 1); // +1 for 'this'
+        self.forget_shared(symbols);
+    }
+
+    fn emit_string(&mut self, s: &str) {
+        let idx = self.add_constant(s.to_string());
+        self.emit_byte(OpCode::LoadConst.into());
+        self.emit_int(idx as i32);
+    }
+
+    // Replaces the value on the stack by what 'on_null' pushes if it is null, or else applies 'non_null' to it
+    fn emit_unless_null(&mut self, on_null: impl FnOnce(&mut Self), non_null: impl FnOnce(&mut Self)) {
+        self.emit_byte(OpCode::Dup.into());
+        self.emit_byte(OpCode::Push.into());
+        self.emit_int(0);
+        self.emit_byte(OpCode::Equal.into());
+        let not_null = self.emit_jump(OpCode::JumpIfFalse.into());
+        self.emit_byte(OpCode::Pop.into());
+        on_null(self);
+        let end = self.emit_jump(OpCode::Jump.into());
+        self.patch_jump(not_null);
+        non_null(self);
+        self.patch_jump(end);
+    }
+
+    // Stores the value on the stack in a hidden variable of the current scope
+    fn emit_temp(&mut self, name: &str, ty: &Type, symbols: &mut SymbolTable) -> VarSlot {
+        let slot = symbols.declare(name, ty.clone(), false);
+        self.emit_store_var(slot.clone());
+        slot
+    }
+
+    // Pushes element 'index' of the tuple held in 'slot'
+    fn emit_load_element(&mut self, slot: &VarSlot, index: usize) {
+        self.emit_load_slot(slot);
+        self.emit_byte(OpCode::Push.into());
+        self.emit_int(index as i32);
+        self.emit_byte(OpCode::LoadArray.into());
+    }
+
+    // Whether two values of the type are equal exactly when their stack values are (references for objects)
+    fn plain_equality(&self, ty: &Type, symbols: &SymbolTable) -> bool {
+        match ty {
+            Type::Tuple(_) => false,
+            Type::Nullable(inner) => self.plain_equality(inner, symbols),
+            _ => !self.has_method(ty, "equals", std::slice::from_ref(ty), &Type::Int, symbols),
+        }
+    }
+
+    // a, b -> 1 if equal, else 0
+    fn emit_value_equals(&mut self, ty: &Type, symbols: &mut SymbolTable) {
+        if self.plain_equality(ty, symbols) {
+            self.emit_byte(OpCode::Equal.into()); // Strings compare by content
+            return;
+        }
+        symbols.push_scope();
+        let b = self.emit_temp("$right", ty, symbols);
+        let a = self.emit_temp("$left", ty, symbols);
+        match ty {
+            Type::Nullable(inner) => {
+                // Equal if both are null, unequal if only one is
+                let mut ends = Vec::new();
+                for (first, second) in [(&a, &b), (&b, &a)] {
+                    self.emit_load_slot(first);
+                    self.emit_byte(OpCode::Push.into());
+                    self.emit_int(0);
+                    self.emit_byte(OpCode::Equal.into());
+                    let not_null = self.emit_jump(OpCode::JumpIfFalse.into());
+                    self.emit_load_slot(second);
+                    self.emit_byte(OpCode::Push.into());
+                    self.emit_int(0);
+                    self.emit_byte(OpCode::Equal.into());
+                    ends.push(self.emit_jump(OpCode::Jump.into()));
+                    self.patch_jump(not_null);
                 }
-                self.emit_expr(index, symbols);
-                self.emit_expr(value, symbols);
-                self.emit_byte(OpCode::StoreArray.into());
+                self.emit_load_slot(&a);
+                self.emit_load_slot(&b);
+                self.emit_value_equals(inner, symbols);
+                ends.into_iter().for_each(|end| self.patch_jump(end));
             }
-            Stmt::Return(expr) => {
-                self.emit_expr(expr, symbols);
-                self.emit_byte(OpCode::Return.into());
+            Type::Tuple(elems) => {
+                // The product of the element comparisons
+                self.emit_byte(OpCode::Push.into());
+                self.emit_int(1);
+                for (i, elem) in elems.iter().enumerate() {
+                    self.emit_load_element(&a, i);
+                    self.emit_load_element(&b, i);
+                    self.emit_value_equals(elem, symbols);
+                    self.emit_byte(OpCode::Mul.into());
+                }
             }
-            Stmt::Print(expr) => {
-                self.emit_expr(expr, symbols);
-                self.emit_byte(OpCode::Print.into());
+            _ => {
+                self.emit_load_slot(&a);
+                self.emit_load_slot(&b);
+                self.emit_invoke_on_stack("equals", 1, symbols);
             }
-            Stmt::Block(stmts) => {
-                for s in stmts {
-                    self.emit_stmt(s, symbols);
+        }
+        symbols.pop_scope();
+    }
+
+    // value -> int, only meaningful as input to HashCombine
+    fn emit_value_hash(&mut self, ty: &Type, symbols: &mut SymbolTable) {
+        match ty {
+            Type::Str => self.emit_byte(OpCode::HashString.into()),
+            Type::Nullable(inner) => self.emit_unless_null(
+                |e| { e.emit_byte(OpCode::Push.into()); e.emit_int(0); },
+                |e| e.emit_value_hash(inner, symbols),
+            ),
+            Type::Tuple(elems) => {
+                symbols.push_scope();
+                let tuple = self.emit_temp("$tuple", ty, symbols);
+                self.emit_byte(OpCode::Push.into());
+                self.emit_int(17);
+                for (i, elem) in elems.iter().enumerate() {
+                    self.emit_load_element(&tuple, i);
+                    self.emit_value_hash(elem, symbols);
+                    self.emit_byte(OpCode::HashCombine.into());
                 }
+                symbols.pop_scope();
             }
-            Stmt::If(cond, then_branch, else_branch) => {
-                self.emit_expr(cond, symbols);
-                
-                // Jump to Else if false
-                let then_jump = self.emit_jump(OpCode::JumpIfFalse.into());
-                
-                self.emit_stmt(then_branch, symbols);
-                
-                let else_jump = self.emit_jump(OpCode::Jump.into());
-                
-                self.patch_jump(then_jump);
-                
-                if let Some(else_stmt) = else_branch {
-                    self.emit_stmt(else_stmt, symbols);
+            _ if self.has_method(ty, "hashCode", &[], &Type::Int, symbols) => self.emit_invoke_on_stack("hashCode", 0, symbols),
+            _ => {} // Ints and enums are their own hash, other objects hash by reference
+        }
+    }
+
+    // value -> String
+    fn emit_value_text(&mut self, ty: &Type, symbols: &mut SymbolTable) {
+        match ty {
+            Type::Int => self.emit_byte(OpCode::IntToString.into()),
+            Type::Named(name) if self.is_simple_enum(ty, symbols) => {
+                // The name of the variant with that value
+                let variants = symbols.enums[name].variants.clone();
+                symbols.push_scope();
+                let value = self.emit_temp("$enum", ty, symbols);
+                let mut ends = Vec::new();
+                for variant in &variants {
+                    self.emit_load_slot(&value);
+                    self.emit_byte(OpCode::Push.into());
+                    self.emit_int(variant.value);
+                    self.emit_byte(OpCode::Equal.into());
+                    let next = self.emit_jump(OpCode::JumpIfFalse.into());
+                    self.emit_string(&variant.name);
+                    ends.push(self.emit_jump(OpCode::Jump.into()));
+                    self.patch_jump(next);
                 }
-                
-                self.patch_jump(else_jump);
+                self.emit_load_slot(&value);
+                self.emit_byte(OpCode::IntToString.into());
+                ends.into_iter().for_each(|end| self.patch_jump(end));
+                symbols.pop_scope();
             }
-            Stmt::While(cond, body) => {
-                let loop_start = self.code.len();
-                
-                self.emit_expr(cond, symbols);
-                let exit_jump = self.emit_jump(OpCode::JumpIfFalse.into());
-                
-                self.emit_stmt(body, symbols);
-                self.emit_byte(OpCode::Jump.into());
-                
-                let offset = (loop_start as i32) - (self.code.len() as i32) - 4;
-                self.emit_int(offset);
-                
-                self.patch_jump(exit_jump);
+            Type::Str => {}
+            Type::Null => {
+                self.emit_byte(OpCode::Pop.into());
+                self.emit_string("null");
             }
-            Stmt::Expression(expr) => {
-                self.emit_expr(expr, symbols);
-                // An expression used as a statement should have its result popped.
+            Type::Nullable(inner) => self.emit_unless_null(|e| e.emit_string("null"), |e| e.emit_value_text(inner, symbols)),
+            Type::Tuple(elems) => {
+                symbols.push_scope();
+                let tuple = self.emit_temp("$tuple", ty, symbols);
+                self.emit_string("(");
+                for (i, elem) in elems.iter().enumerate() {
+                    if i > 0 {
+                        self.emit_string(", ");
+                        self.emit_byte(OpCode::Add.into());
+                    }
+                    self.emit_load_element(&tuple, i);
+                    self.emit_value_text(elem, symbols);
+                    self.emit_byte(OpCode::Add.into());
+                }
+                self.emit_string(")");
+                self.emit_byte(OpCode::Add.into());
+                symbols.pop_scope();
+            }
+            _ if self.has_method(ty, "toString", &[], &Type::Str, symbols) => self.emit_invoke_on_stack("toString", 0, symbols),
+            _ => {
                 self.emit_byte(OpCode::Pop.into());
+                self.emit_string(&format!("<{}>", ty));
             }
-            Stmt::Function(name, params, body) => {
-                // 1. Jump over the function body so it doesn't execute linearly
-                let jump_over = self.emit_jump(OpCode::Jump.into());
+        }
+    }
 
-                // 2. Record function entry point
-                let entry_point = self.code.len() as u32;
-                if let Some(info) = symbols.functions.get_mut(name) {
-                    info.address = entry_point;
-                }
+    fn null_access(ty: &Type, member: &str) -> ! {
+        panic!("Value of type '{}' may be null: check it with '!= null' first or use '?.{}'", ty, member)
+    }
+
+    // A variable checked against null or with 'is' (or assigned a non-null value) has the narrower type until reassigned
+    fn narrowed_type(&self, name: &str, ty: Type) -> Type {
+        self.narrowed.iter().rev().find(|(n, _)| n == name).map_or(ty, |(_, narrow)| narrow.clone())
+    }
+
+    fn narrow(&mut self, name: &str, ty: Type) {
+        self.narrowed.retain(|(n, _)| n != name);
+        self.narrowed.push((name.to_string(), ty));
+    }
+
+    // Narrows the variable a condition tests, on the branch where it is known to be 'holds':
+    // 'x != null' / 'x == null' drop null from x, 'x is C' makes x a C
+    fn narrow_by_condition(&mut self, cond: &Expr, holds: bool, symbols: &SymbolTable) {
+        let (name, ty) = match cond {
+            Expr::Binary(left, op @ (Op::Equal | Op::NotEqual), right) => {
+                let ((Expr::Variable(name), Expr::Null) | (Expr::Null, Expr::Variable(name))) = (&**left, &**right) else { return };
+                if holds != matches!(op, Op::NotEqual) { return; }
+                let Some(var) = self.lookup_var(name, symbols) else { return };
+                let current = self.narrowed_type(name, var.ty);
+                if !current.is_nullable() { return; }
+                (name, current.non_null())
+            }
+            Expr::InstanceOf(value, target) if holds => {
+                let Expr::Variable(name) = &**value else { return };
+                let Some(var) = self.lookup_var(name, symbols) else { return };
+                // Keep a type that is already more specific ('circle is Shape' leaves a Circle)
+                let current = self.narrowed_type(name, var.ty).non_null();
+                (name, if symbols.is_assignable(&current, target) { current } else { target.clone() })
+            }
+            _ => return,
+        };
+        self.narrow(name, ty);
+    }
+
+    // Control never reaches the statement after this one
+    fn always_exits(stmt: &Stmt) -> bool {
+        match stmt {
+            Stmt::Return(_) | Stmt::Throw(_) | Stmt::Break(_) | Stmt::Continue(_) => true,
+            Stmt::Block(stmts) => stmts.last().is_some_and(Self::always_exits),
+            Stmt::If(_, then_branch, Some(else_branch)) => Self::always_exits(then_branch) && Self::always_exits(else_branch),
+            _ => false,
+        }
+    }
+
+    // Assignments inside a loop, switch, try or match may run before any part of it,
+    // so the null checks of the variables they assign no longer hold there
+    fn forget_assigned(&mut self, stmts: &[Stmt], symbols: &SymbolTable) {
+        let assigned = semant::assigned_variables(stmts);
+        self.narrowed.retain(|(n, _)| !assigned.contains(n));
+        if semant::contains_calls(stmts) {
+            self.forget_shared(symbols);
+        }
+    }
+
+    // A call may run code that assigns globals and static fields: only the checks of locals and parameters survive it
+    fn forget_shared(&mut self, symbols: &SymbolTable) {
+        let narrowed = std::mem::take(&mut self.narrowed);
+        self.narrowed = narrowed.into_iter()
+            .filter(|(n, _)| matches!(symbols.lookup(n).map(|v| &v.slot), Some(VarSlot::Local(_))))
+            .collect();
+    }
+
+    // Plain variants push their value; variants of data-carrying enums allocate [tag, payload...]
+    fn emit_variant(&mut self, info: &EnumInfo, variant_name: &str, args: &[Expr], symbols: &mut SymbolTable) -> Type {
+        let variant = info.variant(variant_name).clone();
+        if args.len() != variant.fields.len() {
+            panic!("Variant '{}.{}' takes {} values, got {}", info.name, variant.name, variant.fields.len(), args.len());
+        }
+        if !info.has_payloads() {
+            self.emit_byte(OpCode::Push.into());
+            self.emit_int(variant.value);
+            return Type::Named(info.name.clone());
+        }
+
+        self.emit_byte(OpCode::NewInstance.into());
+        let name_idx = self.add_constant(info.name.clone());
+        self.emit_int(name_idx as i32);
+        self.emit_int(variant.fields.len() as i32 + 1);
+
+        self.emit_byte(OpCode::Dup.into());
+        self.emit_byte(OpCode::Push.into());
+        self.emit_int(variant.value);
+        self.emit_byte(OpCode::SetField.into());
+        self.emit_int(0);
+        for (i, (arg, field_ty)) in args.iter().zip(&variant.fields).enumerate() {
+            self.emit_byte(OpCode::Dup.into());
+            let arg_ty = self.emit_expr(arg, symbols);
+            self.expect_type(symbols, &arg_ty, field_ty, &format!("value {} of '{}.{}'", i + 1, info.name, variant.name));
+            self.emit_byte(OpCode::SetField.into());
+            self.emit_int(i as i32 + 1);
+        }
+        Type::Named(info.name.clone())
+    }
+
+    fn is_data_enum(&self, ty: &Type, symbols: &SymbolTable) -> bool {
+        matches!(ty, Type::Named(name) if symbols.enums.get(name).is_some_and(|e| e.has_payloads()))
+    }
+
+    // Lowered to a chain of tests on a hidden copy of the value; the first matching arm wins.
+    // Patterns are checked for type, reachability and exhaustiveness along the way.
+    fn emit_match(&mut self, scrutinee: &Expr, arms: &[MatchArm], symbols: &mut SymbolTable) -> Type {
+        let value_ty = self.emit_expr(scrutinee, symbols);
+        let enum_info = match &value_ty {
+            Type::Named(name) => symbols.enums.get(name).cloned(),
+            _ => None,
+        };
+        let tagged = enum_info.as_ref().is_some_and(|e| e.has_payloads());
+        let arm_bodies: Vec<Stmt> = arms.iter().map(|a| a.body.clone()).collect();
+        self.forget_assigned(&arm_bodies, symbols);
+        let before = self.narrowed.clone();
 
-                // Setup locals for emission
-                let old_locals = symbols.locals.clone();
-                let old_local_index = symbols.next_local_index;
-                symbols.locals.clear();
-                symbols.next_local_index = 0;
+        symbols.push_scope();
+        let temp = symbols.declare("$match", value_ty.clone(), false);
+        self.emit_store_var(temp.clone());
 
-                for param in params {
-                    symbols.locals.insert(param.clone(), symbols.next_local_index);
-                    symbols.next_local_index += 1;
+        let mut covered: Vec<String> = Vec::new(); // Variants or literals already handled
+        let mut catch_all = false;
+        let mut result_ty: Option<Type> = None;
+        let mut end_jumps = Vec::new();
+
+        for arm in arms {
+            if catch_all {
+                panic!("Unreachable match arm after a catch-all pattern");
+            }
+            symbols.push_scope();
+            self.narrowed = before.clone();
+            match &arm.pattern {
+                Pattern::Variant(_, _, names) => self.narrowed.retain(|(n, _)| !names.contains(n)),
+                Pattern::Binding(name) => self.narrowed.retain(|(n, _)| n != name),
+                _ => {}
+            }
+            let skip_jump = match &arm.pattern {
+                Pattern::Integer(_) | Pattern::StringLiteral(_) => {
+                    let (literal, key) = match &arm.pattern {
+                        Pattern::Integer(n) => (Expr::Integer(*n), n.to_string()),
+                        Pattern::StringLiteral(s) => (Expr::StringLiteral(s.clone()), format!("{:?}", s)),
+                        _ => unreachable!(),
+                    };
+                    if covered.contains(&key) {
+                        panic!("Duplicate match pattern {}", key);
+                    }
+                    covered.push(key);
+                    self.emit_load_slot(&temp);
+                    let literal_ty = self.emit_expr(&literal, symbols);
+                    if literal_ty != value_ty {
+                        panic!("Pattern of type '{}' cannot match a value of type '{}'", literal_ty, value_ty);
+                    }
+                    self.emit_byte(OpCode::Equal.into());
+                    Some(self.emit_jump(OpCode::JumpIfFalse.into()))
                 }
+                Pattern::Variant(enum_name, variant_name, bindings) => {
+                    let info = enum_info.as_ref().filter(|e| e.name == *enum_name)
+                        .unwrap_or_else(|| panic!("Pattern '{}.{}' cannot match a value of type '{}'", enum_name, variant_name, value_ty));
+                    let variant: VariantInfo = info.variant(variant_name).clone();
+                    if bindings.len() != variant.fields.len() {
+                        panic!("Pattern '{}.{}' must bind {} values, got {}", enum_name, variant_name, variant.fields.len(), bindings.len());
+                    }
+                    if covered.contains(variant_name) {
+                        panic!("Duplicate match pattern {}.{}", enum_name, variant_name);
+                    }
+                    covered.push(variant_name.clone());
 
-                // 3. Emit Body
-                for s in body {
-                    self.emit_stmt(s, symbols);
+                    // Compare the tag (field 0 of data-carrying enums, the value itself otherwise)
+                    self.emit_load_slot(&temp);
+                    if tagged {
+                        self.emit_byte(OpCode::GetField.into());
+                        self.emit_int(0);
+                    }
+                    self.emit_byte(OpCode::Push.into());
+                    self.emit_int(variant.value);
+                    self.emit_byte(OpCode::Equal.into());
+                    let jump = self.emit_jump(OpCode::JumpIfFalse.into());
+
+                    for (i, (binding, field_ty)) in bindings.iter().zip(&variant.fields).enumerate() {
+                        if binding == "_" {
+                            continue;
+                        }
+                        self.emit_load_slot(&temp);
+                        self.emit_byte(OpCode::GetField.into());
+                        self.emit_int(i as i32 + 1);
+                        let slot = symbols.declare(binding, field_ty.clone(), false);
+                        self.emit_store_var(slot);
+                    }
+                    Some(jump)
                 }
-                
-                self.emit_byte(OpCode::Return.into()); // Implicit return
-                self.patch_jump(jump_over);
-
-                // Restore locals
-                symbols.locals = old_locals;
-                symbols.next_local_index = old_local_index;
-            }
-            Stmt::Class(name, fields, methods) => {
-                // Register class in symbol table
-                let mut field_map = HashMap::new();
-                for (i, f) in fields.iter().enumerate() {
-                    field_map.insert(f.clone(), i as u32);
-                }
-                
-                let mut method_names = Vec::new();
-                for m in methods {
-                    if let Stmt::Function(fname, _, _) = m {
-                        // fname is "Class_Method", strip prefix to get "Method"
-                        let short_name = fname.strip_prefix(&format!("{}_", name)).unwrap_or(fname);
-                        method_names.push(short_name.to_string());
+                Pattern::Binding(name) => {
+                    if name != "_" {
+                        self.emit_load_slot(&temp);
+                        let slot = symbols.declare(name, value_ty.clone(), false);
+                        self.emit_store_var(slot);
                     }
+                    catch_all = true;
+                    None
                 }
+            };
 
-                symbols.classes.insert(name.clone(), ClassInfo {
-                    name: name.clone(),
-                    fields: field_map,
-                    methods: method_names,
-                });
-
-                // Emit methods
-                for method in methods {
-                    self.emit_stmt(&method, symbols);
+            // Every arm leaves one value behind; block arms leave a placeholder
+            let arm_ty = match &arm.body {
+                Stmt::Expression(expr) => self.emit_expr(expr, symbols),
+                block => {
+                    // Temporaries of the enclosing expression sit on the stack, so no handler may cut it back
+                    self.match_depth += 1;
+                    self.emit_stmt(block, symbols);
+                    self.match_depth -= 1;
+                    self.emit_byte(OpCode::Push.into());
+                    self.emit_int(0);
+                    Type::Void
+                }
+            };
+            result_ty = Some(match result_ty {
+                None => arm_ty,
+                Some(Type::Void) => Type::Void,
+                Some(_) if arm_ty == Type::Void => Type::Void,
+                // An arm yielding null makes the result nullable
+                Some(Type::Null) if self.can_be_null(&arm_ty, symbols) => arm_ty.nullable(),
+                Some(expected) if arm_ty == Type::Null && self.can_be_null(&expected, symbols) => expected.nullable(),
+                Some(expected) => {
+                    self.expect_type(symbols, &arm_ty, &expected, "match arm");
+                    expected
                 }
+            });
+            symbols.pop_scope();
+
+            end_jumps.push(self.emit_jump(OpCode::Jump.into()));
+            if let Some(jump) = skip_jump {
+                self.patch_jump(jump);
             }
-            Stmt::FieldSet(obj, field, value) => {
-                self.emit_expr(obj, symbols);   // Push object ref
-                self.emit_expr(value, symbols); // Push value to assign
-                
-                // Resolve field index
-                let mut field_idx = None;
-                for cls in symbols.classes.values() {
-                    if let Some(idx) = cls.fields.get(field) {
-                        field_idx = Some(*idx);
-                        break;
+        }
+
+        if !catch_all {
+            match &enum_info {
+                Some(info) => {
+                    let missing: Vec<String> = info.variants.iter()
+                        .filter(|v| !covered.contains(&v.name))
+                        .map(|v| format!("{}.{}", info.name, v.name))
+                        .collect();
+                    if !missing.is_empty() {
+                        panic!("Non-exhaustive match: missing {}", missing.join(", "));
                     }
                 }
-                let idx = field_idx.expect(&format!("Field '{}' not found in any known class", field));
-                
-                self.emit_byte(OpCode::SetField.into());
-                self.emit_int(idx as i32);
+                None => panic!("Non-exhaustive match on '{}': add a '_' arm", value_ty),
             }
         }
+
+        // Only reachable for values forged with Enum.from(n) that name no variant
+        self.emit_byte(OpCode::Push.into());
+        self.emit_int(0);
+        for jump in end_jumps {
+            self.patch_jump(jump);
+        }
+        symbols.pop_scope();
+        self.narrowed = before;
+        result_ty.unwrap_or_else(|| panic!("match needs at least one arm"))
     }
 
-    pub fn write_file(&self, path: &str) -> std::io::Result<()> {
-        let file = File::create(path)?;
-        let mut writer = BufWriter::new(file);
-        writer.write_all(b"AMBR")?; // Magic
-        writer.write_all(&1u16.to_le_bytes())?; // Version
-        writer.write_all(&0u32.to_le_bytes())?; // Entry point placeholder
-        
-        // Write Constant Pool
-        writer.write_all(&(self.constants.len() as u32).to_le_bytes())?;
-        for s in &self.constants {
+    // Dense int cases become a single TableSwitch; sparse or string cases a chain of comparisons
+    fn emit_switch(&mut self, subject: &Expr, cases: &[(Vec<Expr>, Vec<Stmt>)], default: &Option<Vec<Stmt>>, symbols: &mut SymbolTable) {
+        let subject_ty = self.emit_expr(subject, symbols);
+        if !matches!(subject_ty, Type::Int | Type::Str) {
+            panic!("switch needs an int or String value, found '{}'", subject_ty);
+        }
+
+        // Case values are literals or consts of the subject's type, each used once
+        let mut values: Vec<Vec<Expr>> = Vec::new();
+        let mut seen: Vec<String> = Vec::new();
+        for (case_values, _) in cases {
+            let mut folded = Vec::new();
+            for v in case_values {
+                let value = match v {
+                    Expr::Integer(_) | Expr::StringLiteral(_) => v.clone(),
+                    Expr::Variable(name) => match symbols.lookup(name).map(|var| &var.slot) {
+                        Some(VarSlot::Const(value)) => value.clone(),
+                        _ => panic!("Case value '{}' is not a const", name),
+                    },
+                    _ => panic!("Case values must be literals or consts"),
+                };
+                let (value_ty, key) = match &value {
+                    Expr::Integer(n) => (Type::Int, n.to_string()),
+                    Expr::StringLiteral(s) => (Type::Str, format!("{:?}", s)),
+                    _ => unreachable!(),
+                };
+                if value_ty != subject_ty {
+                    panic!("Case value {} does not match the switch type '{}'", key, subject_ty);
+                }
+                if seen.contains(&key) {
+                    panic!("Duplicate case value {}", key);
+                }
+                seen.push(key);
+                folded.push(value);
+            }
+            values.push(folded);
+        }
+
+        self.loops.push(LoopContext { label: None, start: 0, break_jumps: Vec::new(), is_switch: true, try_depth: self.tries.len() });
+        let mut end_jumps = Vec::new();
+
+        let ints: Vec<(i32, usize)> = values.iter().enumerate()
+            .flat_map(|(case, vals)| vals.iter().filter_map(move |v| match v { Expr::Integer(n) => Some((*n, case)), _ => None }))
+            .collect();
+        let low = ints.iter().map(|(n, _)| *n).min().unwrap_or(0);
+        let high = ints.iter().map(|(n, _)| *n).max().unwrap_or(0);
+        let span = high as i64 - low as i64 + 1;
+
+        if subject_ty == Type::Int && ints.len() >= 3 && span <= 2 * ints.len() as i64 {
+            // Every value in low..=high gets a slot; gaps go to the default
+            self.emit_byte(OpCode::TableSwitch.into());
+            self.emit_int(low);
+            self.emit_int(span as i32);
+            let default_operand = self.code.len();
+            self.emit_int(0);
+            let table = self.code.len();
+            for _ in 0..span {
+                self.emit_int(0);
+            }
+            let table_end = self.code.len();
+
+            let mut targets = Vec::new();
+            for (_, body) in cases {
+                targets.push(self.code.len());
+                self.emit_block_body(body, symbols);
+                end_jumps.push(self.emit_jump(OpCode::Jump.into()));
+            }
+            let default_target = self.code.len();
+            if let Some(body) = default {
+                self.emit_block_body(body, symbols);
+            }
+
+            let relative = |target: usize| (target as i32 - table_end as i32).to_le_bytes();
+            self.code[default_operand..default_operand + 4].copy_from_slice(&relative(default_target));
+            for (n, case) in &ints {
+                let slot = table + (*n as i64 - low as i64) as usize * 4;
+                self.code[slot..slot + 4].copy_from_slice(&relative(targets[*case]));
+            }
+            for k in 0..span as usize {
+                let slot = table + k * 4;
+                if !ints.iter().any(|(n, _)| (*n as i64 - low as i64) as usize == k) {
+                    self.code[slot..slot + 4].copy_from_slice(&relative(default_target));
+                }
+            }
+        } else {
+            symbols.push_scope();
+            let temp = symbols.declare("$switch", subject_ty.clone(), false);
+            self.emit_store_var(temp.clone());
+
+            for (case_values, (_, body)) in values.iter().zip(cases) {
+                // (subject == a) + (subject == b) + ... is non-zero iff one of the values matches
+                for (i, value) in case_values.iter().enumerate() {
+                    self.emit_load_slot(&temp);
+                    self.emit_expr(value, symbols);
+                    self.emit_byte(OpCode::Equal.into());
+                    if i > 0 {
+                        self.emit_byte(OpCode::Add.into());
+                    }
+                }
+                let next_case = self.emit_jump(OpCode::JumpIfFalse.into());
+                self.emit_block_body(body, symbols);
+                end_jumps.push(self.emit_jump(OpCode::Jump.into()));
+                self.patch_jump(next_case);
+            }
+            if let Some(body) = default {
+                self.emit_block_body(body, symbols);
+            }
+            symbols.pop_scope();
+        }
+
+        let ctx = self.loops.pop().unwrap();
+        for jump in end_jumps.into_iter().chain(ctx.break_jumps) {
+            self.patch_jump(jump);
+        }
+    }
+
+    // Bodies of cases, try, catch and finally: null checks made inside don't outlive them
+    fn emit_block_body(&mut self, body: &[Stmt], symbols: &mut SymbolTable) {
+        let outer_narrowed = self.narrowed.clone();
+        symbols.push_scope();
+        for s in body {
+            self.emit_stmt(s, symbols);
+        }
+        symbols.pop_scope();
+        self.narrowed = outer_narrowed;
+    }
+
+    // Each catch and the finally block become exception table rows. The finally block is copied onto
+    // every way out: normal completion, the end of each catch, early exits, and a catch-all handler
+    // that runs it and rethrows.
+    fn emit_try(&mut self, body: &[Stmt], catches: &[CatchClause], finally: &Option<Vec<Stmt>>, symbols: &mut SymbolTable) {
+        if self.match_depth > 0 {
+            panic!("'try' is not allowed inside a match arm");
+        }
+        let exception = Type::Named("Exception".to_string());
+        for (i, clause) in catches.iter().enumerate() {
+            self.check_type_exists(&clause.ty, symbols);
+            if !symbols.is_assignable(&clause.ty, &exception) || symbols.interfaces.contains_key(&clause.ty.to_string()) {
+                panic!("Cannot catch '{}': it is not an Exception class", clause.ty);
+            }
+            if let Some(earlier) = catches[..i].iter().find(|c| symbols.is_assignable(&clause.ty, &c.ty)) {
+                panic!("Unreachable catch clause: '{}' is already caught by the clause for '{}'", clause.ty, earlier.ty);
+            }
+        }
+
+        let mut end_jumps = Vec::new();
+        let mut protected = Vec::new(); // Ranges the finally block guards: the body and every catch
+
+        self.tries.push(TryContext { finally: finally.clone(), gaps: Vec::new(), caught: None });
+        let try_start = self.code.len();
+        self.emit_block_body(body, symbols);
+        let body_ranges = Self::protected_ranges(try_start, self.code.len(), &self.tries.pop().unwrap().gaps);
+        self.emit_finally_copy(finally, symbols);
+        end_jumps.push(self.emit_jump(OpCode::Jump.into()));
+
+        for clause in catches {
+            let target = self.code.len();
+            let class = self.add_constant(clause.ty.to_string()) as i32;
+            for (start, end) in &body_ranges {
+                self.handlers.push(Handler { start: *start as u32, end: *end as u32, target: target as u32, class });
+            }
+
+            // The handler starts with the exception on the stack
+            symbols.push_scope();
+            let slot = symbols.declare(&clause.name, clause.ty.clone(), false);
+            self.emit_store_var(slot.clone());
+            self.tries.push(TryContext { finally: finally.clone(), gaps: Vec::new(), caught: Some(slot) });
+            self.emit_block_body(&clause.body, symbols);
+            let ctx = self.tries.pop().unwrap();
+            symbols.pop_scope();
+            protected.extend(Self::protected_ranges(target, self.code.len(), &ctx.gaps));
+
+            self.emit_finally_copy(finally, symbols);
+            end_jumps.push(self.emit_jump(OpCode::Jump.into()));
+        }
+
+        if finally.is_some() {
+            let target = self.code.len() as u32;
+            for (start, end) in body_ranges.into_iter().chain(protected) {
+                self.handlers.push(Handler { start: start as u32, end: end as u32, target, class: -1 });
+            }
+            symbols.push_scope();
+            let slot = symbols.declare("$exception", exception, false);
+            self.emit_store_var(slot.clone());
+            self.emit_finally_copy(finally, symbols);
+            self.emit_load_slot(&slot);
+            self.emit_byte(OpCode::Rethrow.into());
+            symbols.pop_scope();
+        }
+
+        for jump in end_jumps {
+            self.patch_jump(jump);
+        }
+    }
+
+    fn emit_finally_copy(&mut self, finally: &Option<Vec<Stmt>>, symbols: &mut SymbolTable) {
+        if let Some(body) = finally {
+            self.emit_block_body(body, symbols);
+        }
+    }
+
+    // Runs the finally blocks of the tries a break/continue/return leaves, innermost first
+    fn emit_finally_exits(&mut self, depth: usize, symbols: &mut SymbolTable) {
+        for i in (depth..self.tries.len()).rev() {
+            if self.tries[i].finally.is_none() {
+                continue;
+            }
+            // The copy is emitted as if it stood where the finally block does: outside try i and its loops
+            let exited = self.tries.split_off(i);
+            let inner_loops = self.loops.iter().position(|l| l.try_depth > i).unwrap_or(self.loops.len());
+            let exited_loops = self.loops.split_off(inner_loops);
+
+            let start = self.code.len();
+            self.emit_finally_copy(&exited[0].finally, symbols);
+            let end = self.code.len();
+
+            self.loops.extend(exited_loops);
+            self.tries.extend(exited);
+            for ctx in &mut self.tries[i..] {
+                ctx.gaps.push((start, end));
+            }
+        }
+    }
+
+    // [start, end) without the gaps
+    fn protected_ranges(start: usize, end: usize, gaps: &[(usize, usize)]) -> Vec<(usize, usize)> {
+        let mut gaps = gaps.to_vec();
+        gaps.sort();
+        let mut ranges = Vec::new();
+        let mut from = start;
+        for (gap_start, gap_end) in gaps {
+            if gap_start > from {
+                ranges.push((from, gap_start));
+            }
+            from = from.max(gap_end);
+        }
+        if end > from {
+            ranges.push((from, end));
+        }
+        ranges
+    }
+
+    // Calls the function value just pushed (it stays below the arguments)
+    fn emit_indirect_call(&mut self, callee_ty: &Type, args: &[Expr], callee: &str, symbols: &mut SymbolTable) -> Type {
+        if callee_ty.is_nullable() {
+            panic!("{} may be null: check it with '!= null' before calling it", callee);
+        }
+        let Type::Function(params, return_type) = callee_ty else {
+            panic!("{} is not a function, it has type '{}'", callee, callee_ty);
+        };
+        let args = self.resolve_args(args, None, 0, callee, symbols);
+        self.emit_args(&args, params, callee, symbols);
+        self.emit_byte(OpCode::CallIndirect.into());
+        self.emit_byte(args.len() as u8);
+        self.forget_shared(symbols);
+        (**return_type).clone()
+    }
+
+    // A lambda is emitted in place as an anonymous function. The enclosing function's locals it uses
+    // are copied into the closure when it is created and arrive as extra, read-only parameters.
+    fn emit_lambda(&mut self, params: &[Param], body: &[Stmt], symbols: &mut SymbolTable) -> Type {
+        let captures: Vec<(String, Variable)> = semant::free_variables(params, body).into_iter()
+            .filter_map(|name| {
+                // File-scope globals are shared; block-scoped ones are copied like locals, since their slots get reused
+                let mut var = symbols.lookup(&name).filter(|v| !matches!(v.slot, VarSlot::Global(_)) || !symbols.is_file_variable(&name))?.clone();
+                var.ty = self.narrowed_type(&name, var.ty); // The copy keeps the value it was checked to have
+                Some((name, var))
+            })
+            .collect();
+
+        let name = format!("$lambda{}", self.lambda_count);
+        self.lambda_count += 1;
+        let mut frame_params = params.to_vec();
+        frame_params.extend(captures.iter().map(|(n, v)| Param { name: n.clone(), ty: v.ty.clone(), mutable: false, default: None, variadic: false }));
+        let signature = Signature { params: frame_params.iter().map(|p| p.ty.clone()).collect(), return_type: Type::Infer };
+        symbols.functions.insert(name.clone(), FunctionInfo {
+            name: name.clone(), address: 0, local_count: 0, signature, visibility: Visibility::Public, type_params: Vec::new(),
+            param_names: frame_params.iter().map(|p| p.name.clone()).collect(), defaults: vec![None; frame_params.len()], variadic: false,
+        });
+
+        // The return type comes from the first 'return' (void if there is none)
+        let decl = FunctionDecl { name: name.clone(), params: frame_params, return_type: Type::Infer, body: body.to_vec() };
+        let outer_captured = std::mem::replace(&mut self.captured, captures.iter().map(|(n, _)| n.clone()).collect());
+        self.emit_function(&decl, symbols);
+        self.captured = outer_captured;
+        let info = symbols.functions.get_mut(&name).unwrap();
+        if info.signature.return_type == Type::Infer {
+            info.signature.return_type = Type::Void;
+        }
+        let (address, return_type) = (info.address, info.signature.return_type.clone());
+
+        for (_, var) in &captures {
+            self.emit_load(var.clone(), symbols);
+        }
+        self.emit_byte(OpCode::MakeClosure.into());
+        self.emit_int(address as i32);
+        self.emit_byte(captures.len() as u8);
+        Type::Function(params.iter().map(|p| p.ty.clone()).collect(), Box::new(return_type))
+    }
+
+    fn emit_static_call(&mut self, owner: &str, method: &str, args: &[Expr], symbols: &mut SymbolTable) -> Type {
+        let full_name = format!("{}_{}", owner, method);
+        let sig = symbols.functions[&full_name].signature.clone();
+        let visibility = symbols.functions[&full_name].visibility;
+        let callee = format!("method '{}.{}'", owner, method);
+        self.check_access(owner, visibility, &callee, symbols);
+        let args = self.resolve_args(args, symbols.functions.get(&full_name).cloned(), 0, &callee, symbols);
+        self.emit_args(&args, &sig.params, &callee, symbols);
+        self.emit_byte(OpCode::Call.into());
+        self.calls_to_patch.push((self.code.len(), full_name));
+        self.emit_int(0);
+        self.emit_byte(args.len() as u8);
+        self.forget_shared(symbols);
+        sig.return_type
+    }
+
+    // 'Name' in Name.member refers to a class or enum (static access) unless a variable shadows it;
+    // so does 'module.Name'
+    fn static_receiver(&self, obj: &Expr, symbols: &SymbolTable) -> Option<String> {
+        let name = match obj {
+            Expr::Variable(name) if self.lookup_var(name, symbols).is_none() => self.scope.resolve(name).unwrap_or(name).to_string(),
+            Expr::GetField(module, member) => self.module_member(module, member, symbols)?,
+            _ => return None,
+        };
+        (symbols.classes.contains_key(&name) || symbols.enums.contains_key(&name)).then_some(name)
+    }
+
+    // 'module.name' names a top-level declaration of an imported module, unless a variable shadows the module
+    fn module_member(&self, obj: &Expr, member: &str, symbols: &SymbolTable) -> Option<String> {
+        let Expr::Variable(alias) = obj else { return None };
+        if symbols.lookup(alias).is_some() {
+            return None;
+        }
+        let module = self.scope.modules.get(alias)?;
+        let info = &symbols.modules[module];
+        if info.private.iter().any(|p| p == member) {
+            panic!("Function '{}' is private to module '{}'", member, module);
+        }
+        let name = info.exports.get(member)
+            .unwrap_or_else(|| panic!("Module '{}' has no top-level declaration '{}'", module, member));
+        Some(name.clone())
+    }
+
+    // A module's file-scope variables are qualified like its other top-level names
+    fn global_name(&self, name: &str, symbols: &SymbolTable) -> String {
+        if symbols.at_file_scope() { self.scope.qualify(name) } else { name.to_string() }
+    }
+
+    // Variables in scope first, then the static fields of the class being emitted, then the globals
+    // of the module and its imports
+    fn lookup_var(&self, name: &str, symbols: &SymbolTable) -> Option<Variable> {
+        symbols.lookup(name).cloned().or_else(|| {
+            let class_name = self.current_class.as_ref()?;
+            let field = symbols.find_static_field(class_name, name)?;
+            self.check_access(&field.owner, field.visibility, &format!("field '{}.{}'", field.owner, name), symbols);
+            self.check_initialized(field, name);
+            Some(field.var.clone())
+        }).or_else(|| symbols.lookup(self.scope.resolve(name)?).cloned())
+    }
+
+    fn resolve_static_field(&self, class_name: &str, field_name: &str, symbols: &SymbolTable) -> StaticField {
+        let field = symbols.find_static_field(class_name, field_name).cloned()
+            .unwrap_or_else(|| panic!("Class '{}' has no static field '{}'", class_name, field_name));
+        self.check_access(&field.owner, field.visibility, &format!("field '{}.{}'", field.owner, field_name), symbols);
+        self.check_initialized(&field, field_name);
+        field
+    }
+
+    // A static initializer cannot use its own field or one declared after it
+    fn check_initialized(&self, field: &StaticField, name: &str) {
+        if self.current_class.as_deref() == Some(field.owner.as_str()) && self.pending_statics.iter().any(|f| f == name) {
+            panic!("Static field '{}.{}' is used before it is initialized (static initializers run in declaration order)", field.owner, name);
+        }
+    }
+
+    // Private members are only reachable from inside their declaring class, protected ones from subclasses too
+    fn check_access(&self, owner: &str, visibility: Visibility, member: &str, symbols: &SymbolTable) {
+        let allowed = match visibility {
+            Visibility::Public => true,
+            Visibility::Private => self.current_class.as_deref() == Some(owner),
+            Visibility::Protected => self.current_class.as_ref()
+                .is_some_and(|c| symbols.ancestors(c).any(|cls| cls.name == owner)),
+        };
+        if !allowed {
+            panic!("Cannot access {} {} from here", visibility, member);
+        }
+    }
+
+    // Lines the arguments of a call up with the callee's parameters (after the first 'skip', i.e. 'this'):
+    // positional ones first, then named ones, then the default of each parameter left out. Positional
+    // arguments past the others of a variadic function are packed into its last parameter's array;
+    // naming that parameter passes an array instead.
+    // Without a declared signature (interface methods, function values) arguments can only be positional.
+    fn resolve_args(&mut self, args: &[Expr], info: Option<FunctionInfo>, skip: usize, callee: &str, symbols: &mut SymbolTable) -> Vec<Expr> {
+        let positional = args.iter().position(|a| matches!(a, Expr::NamedArg(..))).unwrap_or(args.len());
+        let Some(info) = info else {
+            if let Some(Expr::NamedArg(name, _)) = args.get(positional) {
+                panic!("{} cannot be called with named arguments, got '{}'", callee, name);
+            }
+            return args.to_vec();
+        };
+        let (names, defaults) = (&info.param_names[skip..], &info.defaults[skip..]);
+        let args = &self.hoist_reordered(args, names, symbols);
+        let fixed = names.len() - info.variadic as usize;
+        if positional > names.len() && !info.variadic {
+            return args.to_vec(); // Reported by emit_args
+        }
+        let mut slots: Vec<Option<Expr>> = args[..positional.min(fixed)].iter().cloned().map(Some).collect();
+        slots.resize(names.len(), None);
+        if info.variadic && positional > fixed {
+            slots[fixed] = Some(Expr::VarArgs(args[fixed..positional].to_vec()));
+        }
+        for arg in &args[positional..] {
+            let Expr::NamedArg(name, value) = arg else {
+                panic!("Positional arguments of {} must come before named ones", callee);
+            };
+            let i = names.iter().position(|n| n == name)
+                .unwrap_or_else(|| panic!("{} has no parameter '{}'", callee, name));
+            if slots[i].is_some() {
+                panic!("Argument '{}' of {} is given twice", name, callee);
+            }
+            slots[i] = Some((**value).clone());
+        }
+        let mut resolved = Vec::new();
+        for (i, slot) in slots.into_iter().enumerate() {
+            match (slot, &defaults[i]) {
+                (Some(arg), _) => resolved.push(arg),
+                (None, _) if info.variadic && i == fixed => resolved.push(Expr::VarArgs(Vec::new())),
+                (None, Some(default)) => resolved.push(self.default_value(&names[i], default, symbols)),
+                // Plain positional calls to functions without defaults keep the argument count error
+                (None, None) if positional == args.len() && defaults.iter().all(Option::is_none) && !info.variadic => {
+                    return args.to_vec();
+                }
+                (None, None) => panic!("{} is missing argument '{}'", callee, names[i]),
+            }
+        }
+        resolved
+    }
+
+    // Arguments are evaluated left to right. When named ones are given out of parameter order, each
+    // argument is evaluated into a hidden variable first, so reordering them keeps that order.
+    fn hoist_reordered(&mut self, args: &[Expr], names: &[String], symbols: &mut SymbolTable) -> Vec<Expr> {
+        let targets: Vec<usize> = args.iter()
+            .filter_map(|a| match a { Expr::NamedArg(name, _) => names.iter().position(|n| n == name), _ => None })
+            .collect();
+        if targets.windows(2).all(|w| w[0] < w[1]) {
+            return args.to_vec();
+        }
+        let mut hoist = |emitter: &mut Self, value: &Expr| match value {
+            Expr::Integer(_) | Expr::StringLiteral(_) | Expr::Null => value.clone(),
+            _ => {
+                let ty = emitter.emit_expr(value, symbols);
+                let name = format!("$arg{}", emitter.code.len()); // Unique: every hidden variable is stored at its own offset
+                let slot = symbols.declare(&name, ty, false);
+                emitter.emit_store_var(slot);
+                Expr::Variable(name)
+            }
+        };
+        args.iter().map(|arg| match arg {
+            Expr::NamedArg(name, value) => Expr::NamedArg(name.clone(), Box::new(hoist(self, value))),
+            _ => hoist(self, arg),
+        }).collect()
+    }
+
+    // Pushes call arguments, checking them against the parameter types
+    fn emit_args(&mut self, args: &[Expr], params: &[Type], callee: &str, symbols: &mut SymbolTable) {
+        self.emit_generic_args(args, params, &[], Vec::new(), callee, symbols);
+    }
+
+    // Like emit_args, but parameters may mention type parameters: each is bound to the type of the
+    // first argument in its place (unless given explicitly) and the remaining arguments must agree
+    fn emit_generic_args(&mut self, args: &[Expr], params: &[Type], type_params: &[String], mut bindings: Vec<(String, Type)>,
+                         callee: &str, symbols: &mut SymbolTable) -> Vec<(String, Type)> {
+        if args.len() != params.len() {
+            panic!("{} expects {} arguments, got {}", callee, params.len(), args.len());
+        }
+        for (i, (arg, param)) in args.iter().zip(params).enumerate() {
+            if let (Expr::VarArgs(elems), Type::Array(elem_param)) = (arg, param) {
+                // A new array of the packed arguments, each checked like an argument of its own
+                self.emit_byte(OpCode::Push.into());
+                self.emit_int(elems.len() as i32);
+                self.emit_byte(OpCode::NewArray.into());
+                for (j, elem) in elems.iter().enumerate() {
+                    self.emit_byte(OpCode::Dup.into());
+                    self.emit_byte(OpCode::Push.into());
+                    self.emit_int(j as i32);
+                    let elem_ty = self.emit_expr(elem, symbols);
+                    Self::unify(elem_param, &elem_ty, type_params, &mut bindings);
+                    self.expect_type(symbols, &elem_ty, &elem_param.substitute(&bindings), &format!("argument {} of {}", i + j + 1, callee));
+                    self.emit_byte(OpCode::StoreArray.into());
+                }
+                continue;
+            }
+            let arg_ty = self.emit_expr(arg, symbols);
+            Self::unify(param, &arg_ty, type_params, &mut bindings);
+            self.expect_type(symbols, &arg_ty, &param.substitute(&bindings), &format!("argument {} of {}", i + 1, callee));
+        }
+        if let Some(missing) = type_params.iter().find(|p| !bindings.iter().any(|(b, _)| b == *p)) {
+            panic!("Cannot infer type parameter '{}' of {}", missing, callee);
+        }
+        // Same order as the declaration, so the bindings can become type arguments
+        type_params.iter().map(|p| bindings.iter().find(|(b, _)| b == p).unwrap().clone()).collect()
+    }
+
+    // Binds the still unbound type parameters of 'param' to the matching parts of 'arg'
+    fn unify(param: &Type, arg: &Type, type_params: &[String], bindings: &mut Vec<(String, Type)>) {
+        match (param, arg) {
+            (Type::Param(p), _) if type_params.contains(p) && !bindings.iter().any(|(b, _)| b == p) => {
+                if *arg == Type::Void { return; }
+                bindings.push((p.clone(), arg.clone()));
+            }
+            (Type::Array(p), Type::Array(a)) => Self::unify(p, a, type_params, bindings),
+            (Type::Tuple(ps), Type::Tuple(args)) if ps.len() == args.len() => {
+                ps.iter().zip(args).for_each(|(p, a)| Self::unify(p, a, type_params, bindings));
+            }
+            (Type::Generic(pn, ps), Type::Generic(an, args)) if pn == an => {
+                ps.iter().zip(args).for_each(|(p, a)| Self::unify(p, a, type_params, bindings));
+            }
+            (Type::Function(ps, pr), Type::Function(args, ar)) if ps.len() == args.len() => {
+                ps.iter().zip(args).for_each(|(p, a)| Self::unify(p, a, type_params, bindings));
+                Self::unify(pr, ar, type_params, bindings);
+            }
+            _ => {}
+        }
+    }
+
+    fn expect_type(&self, symbols: &SymbolTable, actual: &Type, expected: &Type, context: &str) {
+        if !symbols.is_assignable(actual, expected) {
+            panic!("Type mismatch in {}: expected '{}', found '{}'", context, expected, actual);
+        }
+    }
+
+    // Named types must refer to a class, interface or enum known at this point,
+    // generic classes need the right number of type arguments
+    fn check_type_exists(&self, ty: &Type, symbols: &SymbolTable) {
+        match ty {
+            Type::Array(elem) => self.check_type_exists(elem, symbols),
+            Type::Tuple(elems) => elems.iter().for_each(|e| self.check_type_exists(e, symbols)),
+            Type::Function(params, ret) => {
+                params.iter().for_each(|p| self.check_type_exists(p, symbols));
+                self.check_type_exists(ret, symbols);
+            }
+            Type::Named(name) if !symbols.type_exists(name) => {
+                panic!("Unknown type '{}'", name);
+            }
+            Type::Named(name) if symbols.classes.get(name).is_some_and(|c| !c.type_params.is_empty()) => {
+                panic!("Generic class '{}' needs type arguments: {}<{}>", name, name, symbols.classes[name].type_params.join(", "));
+            }
+            Type::Generic(name, args) => {
+                let Some(class) = symbols.classes.get(name) else {
+                    panic!("Unknown generic class '{}'", name);
+                };
+                if class.type_params.len() != args.len() {
+                    panic!("Class '{}' takes {} type arguments, got {}", name, class.type_params.len(), args.len());
+                }
+                args.iter().for_each(|a| self.check_type_exists(a, symbols));
+            }
+            Type::Nullable(inner) => {
+                if !self.can_be_null(inner, symbols) || inner.is_nullable() {
+                    panic!("Type '{}' cannot be nullable: only objects, strings, arrays and functions can be null", inner);
+                }
+                self.check_type_exists(inner, symbols);
+            }
+            Type::Param(name) if !self.type_params.contains(name) => {
+                panic!("Type parameter '{}' is not in scope here", name);
+            }
+            _ => {}
+        }
+    }
+
+    // Fields are looked up in the static class of the object expression
+    fn resolve_field(&self, obj_ty: &Type, field_name: &str, symbols: &SymbolTable) -> FieldInfo {
+        if obj_ty.is_nullable() {
+            Self::null_access(obj_ty, field_name);
+        }
+        let class_info = obj_ty.class_name().and_then(|name| symbols.classes.get(name))
+            .unwrap_or_else(|| panic!("Cannot access field '{}' on a value of type '{}'", field_name, obj_ty));
+        let mut field = class_info.fields.get(field_name).cloned()
+            .unwrap_or_else(|| panic!("Class '{}' has no field '{}'", class_info.name, field_name));
+        self.check_access(&field.owner, field.visibility, &format!("field '{}.{}'", field.owner, field_name), symbols);
+        field.ty = field.ty.substitute(&symbols.type_bindings(obj_ty));
+        field
+    }
+
+    fn emit_load_var(&mut self, name: &str, symbols: &mut SymbolTable) -> Type {
+        let var = self.lookup_var(name, symbols).unwrap_or_else(|| panic!("Undefined variable: {}", name));
+        let ty = self.emit_load(var, symbols);
+        self.narrowed_type(name, ty)
+    }
+
+    fn emit_load(&mut self, var: Variable, symbols: &mut SymbolTable) -> Type {
+        match &var.slot {
+            VarSlot::Const(value) => { self.emit_expr(value, symbols); }
+            slot => self.emit_load_slot(slot),
+        }
+        var.ty
+    }
+
+    fn emit_load_slot(&mut self, slot: &VarSlot) {
+        match slot {
+            VarSlot::Local(index) => {
+                self.emit_byte(OpCode::LoadLocal.into());
+                self.emit_int(*index as i32);
+            }
+            VarSlot::Global(index) => {
+                self.emit_byte(OpCode::LoadGlobal.into());
+                self.emit_int(*index as i32);
+            }
+            VarSlot::Const(_) => unreachable!("consts have no storage"),
+        }
+    }
+
+    fn emit_store_var(&mut self, slot: VarSlot) {
+        match slot {
+            VarSlot::Local(index) => {
+                self.emit_byte(OpCode::StoreLocal.into());
+                self.emit_int(index as i32);
+            }
+            VarSlot::Global(index) => {
+                self.emit_byte(OpCode::StoreGlobal.into());
+                self.emit_int(index as i32);
+            }
+            VarSlot::Const(_) => unreachable!("consts have no storage"),
+        }
+    }
+
+    // Folds a const initializer or default value down to a single literal. Only literals, other consts
+    // and arithmetic on them are allowed; 'context' names what is being folded in errors.
+    fn const_value(&self, expr: &Expr, context: &str, symbols: &SymbolTable) -> Expr {
+        match expr {
+            Expr::Integer(_) | Expr::StringLiteral(_) => expr.clone(),
+            Expr::Variable(name) => match self.lookup_var(name, symbols).map(|v| v.slot) {
+                Some(VarSlot::Const(value)) => value,
+                _ => panic!("{} refers to '{}', which is not a const", context, name),
+            },
+            Expr::Binary(left, op, right) => {
+                match (self.const_value(left, context, symbols), op, self.const_value(right, context, symbols)) {
+                    (Expr::Integer(a), Op::Add, Expr::Integer(b)) => Expr::Integer(a.wrapping_add(b)),
+                    (Expr::Integer(a), Op::Sub, Expr::Integer(b)) => Expr::Integer(a.wrapping_sub(b)),
+                    (Expr::Integer(a), Op::Mul, Expr::Integer(b)) => Expr::Integer(a.wrapping_mul(b)),
+                    (Expr::Integer(_), Op::Div, Expr::Integer(0)) => panic!("{} divides by zero", context),
+                    (Expr::Integer(a), Op::Div, Expr::Integer(b)) => Expr::Integer(a.wrapping_div(b)),
+                    (Expr::Integer(a), Op::LessThan, Expr::Integer(b)) => Expr::Integer((a < b) as i32),
+                    (Expr::Integer(a), Op::Equal, Expr::Integer(b)) => Expr::Integer((a == b) as i32),
+                    (Expr::Integer(a), Op::NotEqual, Expr::Integer(b)) => Expr::Integer((a != b) as i32),
+                    (Expr::StringLiteral(a), Op::Equal, Expr::StringLiteral(b)) => Expr::Integer((a == b) as i32),
+                    (Expr::StringLiteral(a), Op::NotEqual, Expr::StringLiteral(b)) => Expr::Integer((a != b) as i32),
+                    (Expr::StringLiteral(a), Op::Add, Expr::StringLiteral(b)) => Expr::StringLiteral(a + &b),
+                    _ => panic!("{} uses an unsupported operation", context),
+                }
+            }
+            _ => panic!("{} must be a compile-time value", context),
+        }
+    }
+
+    // A parameter's default is a const value or null
+    fn default_value(&self, param: &str, expr: &Expr, symbols: &SymbolTable) -> Expr {
+        match expr {
+            Expr::Null => Expr::Null,
+            _ => self.const_value(expr, &format!("Default value of '{}'", param), symbols),
+        }
+    }
+
+    // Final fields may only be written through 'this' from inside their declaring class's constructor
+    fn check_field_write(&self, obj: &Expr, field_name: &str, field: &FieldInfo) {
+        if !field.is_final {
+            return;
+        }
+        let through_this = matches!(obj, Expr::Variable(name) if name == "this");
+        let constructor = format!("{}_{}", field.owner, field.owner);
+        if !through_this || self.current_function.as_deref() != Some(constructor.as_str()) {
+            panic!("Cannot assign to final field '{}' of class '{}'", field_name, field.owner);
+        }
+    }
+
+    fn emit_function(&mut self, func: &FunctionDecl, symbols: &mut SymbolTable) {
+        let name = &func.name;
+        let outer_type_params = self.type_params.clone();
+        if let Some(info) = symbols.functions.get(name) {
+            self.type_params.extend(info.type_params.iter().cloned());
+        }
+        for param in &func.params {
+            self.check_type_exists(&param.ty, symbols);
+            if let Some(default) = &param.default {
+                let value_ty = match self.default_value(&param.name, default, symbols) {
+                    Expr::Integer(_) => Type::Int,
+                    Expr::StringLiteral(_) => Type::Str,
+                    _ => Type::Null,
+                };
+                self.expect_type(symbols, &value_ty, &param.ty, &format!("default value of '{}'", param.name));
+            }
+        }
+        self.check_type_exists(&func.return_type, symbols);
+
+        // 1. Jump over the function body so it doesn't execute linearly
+        let jump_over = self.emit_jump(OpCode::Jump.into());
+
+        // Loops of the enclosing code are not visible from inside the body
+        let outer_loops = std::mem::take(&mut self.loops);
+        let outer_tries = std::mem::take(&mut self.tries);
+        let outer_handlers = std::mem::take(&mut self.handlers);
+        let outer_narrowed = std::mem::take(&mut self.narrowed);
+
+        // 2. Record function entry point
+        let entry_point = self.code.len() as u32;
+        if let Some(info) = symbols.functions.get_mut(name) {
+            info.address = entry_point;
+        }
+
+        // Setup locals for emission: parameters take the first frame slots
+        let outer_frame = symbols.enter_function(&func.params);
+        let outer_function = self.current_function.replace(name.clone());
+
+        // 3. Reserve the frame's local slots (patched once the body is emitted)
+        self.emit_byte(OpCode::Enter.into());
+        let enter_operand = self.code.len();
+        self.emit_int(0);
+
+        // 4. Emit Body
+        for s in &func.body {
+            self.emit_stmt(s, symbols);
+        }
+
+        // Implicit return: functions always leave exactly one value for the caller
+        self.emit_byte(OpCode::Push.into());
+        self.emit_int(0);
+        self.emit_byte(OpCode::Return.into());
+        self.patch_jump(jump_over);
+
+        // Restore locals
+        let local_count = symbols.exit_function(outer_frame);
+        let extra_slots = (local_count - func.params.len() as u32) as i32;
+        self.code[enter_operand..enter_operand + 4].copy_from_slice(&extra_slots.to_le_bytes());
+        if let Some(info) = symbols.functions.get_mut(name) {
+            info.local_count = local_count;
+        }
+        // Every function gets a table, even an empty one: it keeps the rows of an enclosing try from applying inside it
+        let handlers = std::mem::replace(&mut self.handlers, outer_handlers);
+        let end = self.code.len() as u32;
+        self.exception_tables.push(ExceptionTable { start: entry_point, end, frame_size: local_count, handlers });
+        self.loops = outer_loops;
+        self.tries = outer_tries;
+        self.current_function = outer_function;
+        self.type_params = outer_type_params;
+        self.narrowed = outer_narrowed;
+    }
+
+    fn emit_class(&mut self, decl: &ClassDecl, symbols: &mut SymbolTable) {
+        let name = decl.name.as_str();
+        if symbols.type_exists(name) {
+            panic!("Type '{}' is already declared", name);
+        }
+        // Static fields take global slots for the rest of the program
+        if symbols.in_function() || symbols.variables.len() > 1 {
+            panic!("Class '{}' must be declared at the top level", name);
+        }
+
+        // Inherit the parent's layout: its fields keep their indices and ours are appended
+        let parent_info = decl.parent.as_ref().map(|p| {
+            symbols.classes.get(p).cloned()
+                .unwrap_or_else(|| panic!("Class '{}' extends unknown class '{}' (parents must be declared first)", name, p))
+        });
+        if parent_info.as_ref().is_some_and(|p| p.is_record) {
+            panic!("Record '{}' cannot be extended", decl.parent.as_ref().unwrap());
+        }
+        let mut field_map = parent_info.as_ref().map(|p| p.fields.clone()).unwrap_or_default();
+        let mut static_fields = std::collections::HashMap::new();
+        for f in &decl.fields {
+            if field_map.contains_key(&f.name) || static_fields.contains_key(&f.name) {
+                panic!("Field '{}' is already declared in class '{}' or its parents", f.name, name);
+            }
+            if f.is_static {
+                if f.is_final && f.initializer.is_none() {
+                    panic!("Static final field '{}.{}' needs an initializer", name, f.name);
+                }
+                let var = Variable { slot: symbols.allocate_global(), ty: f.ty.clone(), mutable: !f.is_final };
+                static_fields.insert(f.name.clone(), StaticField { var, owner: name.to_string(), visibility: f.visibility });
+                continue;
+            }
+            let index = field_map.len() as u32;
+            field_map.insert(f.name.clone(), FieldInfo { index, ty: f.ty.clone(), is_final: f.is_final, owner: name.to_string(), visibility: f.visibility });
+        }
+
+        // Methods are registered by name: a second declaration would silently replace the first
+        let mut seen: Vec<&str> = Vec::new();
+        for m in decl.methods.iter().chain(&decl.static_methods) {
+            if seen.contains(&m.name.as_str()) {
+                match m.name.strip_prefix(&format!("{}_", name)).unwrap_or(&m.name) {
+                    short_name if short_name == name => panic!("Constructor of '{}' is declared twice", name),
+                    short_name => panic!("Method '{}.{}' is declared twice", name, short_name),
+                }
+            }
+            seen.push(&m.name);
+        }
+
+        // Constructors start with the parent constructor call, then this class's field initializers.
+        // One is synthesized if the class needs that work done but doesn't declare a constructor.
+        let constructor_name = format!("{}_{}", name, name);
+        let this = || Expr::Variable("this".to_string());
+        // A record's fields are all set by its constructor, whose parameter defaults are their initializers
+        let initializers: Vec<Stmt> = decl.fields.iter()
+            .filter(|f| !f.is_static && !decl.is_record)
+            .filter_map(|f| f.initializer.as_ref().map(|init| Stmt::FieldSet(Box::new(this()), f.name.clone(), init.clone())))
+            .collect();
+        let this_ty = match decl.type_params.is_empty() {
+            true => Type::Named(name.to_string()),
+            false => Type::Generic(name.to_string(), decl.type_params.iter().map(|p| Type::Param(p.clone())).collect()),
+        };
+        let parent_constructor = parent_info.as_ref().and_then(|p| p.constructor.map(|count| (p.name.clone(), count)));
+        let mut methods = decl.methods.clone();
+        if decl.is_record {
+            Self::add_record_members(decl, &mut methods, symbols);
+        }
+        let declared = methods.iter().position(|m| m.name == constructor_name);
+
+        // A field that cannot hold null needs a value before the object is used: its initializer,
+        // or an assignment at the top level of the constructor. Record fields are always set.
+        for f in decl.fields.iter().filter(|f| f.initializer.is_none() && !decl.is_record) {
+            let needs_value = matches!(f.ty, Type::Param(_)) || self.can_be_null(&f.ty, symbols) && !matches!(f.ty, Type::Nullable(_));
+            if !needs_value {
+                continue;
+            }
+            let hint = match f.ty {
+                Type::Param(_) => String::new(),
+                _ => format!(" (declare it '{}?' if it may be null)", f.ty),
+            };
+            if f.is_static {
+                panic!("Static field '{}.{}' of type '{}' needs an initializer{}", name, f.name, f.ty, hint);
+            }
+            let assigned = declared.is_some_and(|i| methods[i].body.iter().any(|s| matches!(s,
+                Stmt::FieldSet(obj, field, _) if *field == f.name && matches!(&**obj, Expr::Variable(v) if v == "this"))));
+            if !assigned {
+                panic!("Field '{}.{}' of type '{}' needs an initializer or an assignment in the constructor{}", name, f.name, f.ty, hint);
+            }
+        }
+
+        if declared.is_some() || !initializers.is_empty() || parent_constructor.is_some() {
+            let index = declared.unwrap_or_else(|| {
+                let receiver = Param { name: "this".to_string(), ty: this_ty.clone(), mutable: false, default: None, variadic: false };
+                methods.push(FunctionDecl { name: constructor_name.clone(), params: vec![receiver], return_type: Type::Void, body: Vec::new() });
+                symbols.functions.insert(constructor_name.clone(), FunctionInfo {
+                    name: constructor_name.clone(),
+                    address: 0,
+                    local_count: 0,
+                    signature: Signature { params: vec![this_ty.clone()], return_type: Type::Void },
+                    visibility: Visibility::Public,
+                    type_params: Vec::new(),
+                    param_names: vec!["this".to_string()],
+                    defaults: vec![None],
+                    variadic: false,
+                });
+                methods.len() - 1
+            });
+            let body = &mut methods[index].body;
+
+            let super_args = match body.first() {
+                Some(Stmt::Expression(Expr::SuperCall(None, args))) => Some(args.clone()),
+                _ => None,
+            };
+            if super_args.is_some() {
+                body.remove(0);
+            }
+            if body.iter().any(|s| matches!(s, Stmt::Expression(Expr::SuperCall(None, _)))) {
+                panic!("super(...) must be the first statement of the constructor");
+            }
+
+            let mut prologue = Vec::new();
+            match (parent_constructor, super_args) {
+                (Some((parent_name, count)), args) => {
+                    if symbols.functions[&format!("{}_{}", parent_name, parent_name)].visibility == Visibility::Private {
+                        panic!("Cannot access private constructor of '{}' from subclass '{}'", parent_name, name);
+                    }
+                    let args = args.unwrap_or_default();
+                    if args.len() != count {
+                        panic!("Constructor of '{}' must call super(...) with {} arguments, got {}", name, count, args.len());
+                    }
+                    let mut call_args = vec![this()];
+                    call_args.extend(args);
+                    prologue.push(Stmt::Expression(Expr::Call(format!("{}_{}", parent_name, parent_name), call_args)));
+                }
+                (None, Some(args)) if args.is_empty() && decl.parent.is_some() => {} // Parent has nothing to construct
+                (None, Some(_)) => panic!("super(...) in '{}' has no matching parent constructor", name),
+                (None, None) => {}
+            }
+            prologue.extend(initializers);
+            body.splice(0..0, prologue);
+        }
+
+        // Register class in symbol table
+        let mut method_names = Vec::new();
+        let mut constructor = None;
+        for m in &methods {
+            // m.name is "Class_Method", strip prefix to get "Method"
+            let short_name = m.name.strip_prefix(&format!("{}_", name)).unwrap_or(&m.name);
+            if short_name == name {
+                constructor = Some(m.params.len() - 1); // Minus the implicit 'this'
+            } else {
+                method_names.push(short_name.to_string());
+            }
+        }
+
+        let mut static_method_names = Vec::new();
+        for m in &decl.static_methods {
+            let short_name = m.name.strip_prefix(&format!("{}_", name)).unwrap_or(&m.name);
+            if short_name == name || method_names.iter().any(|n| n == short_name) {
+                panic!("Static method '{}.{}' clashes with another member of the class", name, short_name);
+            }
+            static_method_names.push(short_name.to_string());
+        }
+
+        // Overrides must keep the signature of the method they replace and may not narrow its visibility
+        let rank = |v: Visibility| match v { Visibility::Private => 0, Visibility::Protected => 1, Visibility::Public => 2 };
+        if let Some(parent) = &decl.parent {
+            for method in &method_names {
+                let Some(inherited) = symbols.method_signature(&Type::Named(parent.clone()), method) else { continue };
+                let owner = &symbols.find_method_owner(parent, method).unwrap().name;
+                let inherited_visibility = symbols.functions[&format!("{}_{}", owner, method)].visibility;
+                if inherited_visibility == Visibility::Private {
+                    continue; // Private methods are not inherited: this one is unrelated
+                }
+                let own = &symbols.functions[&format!("{}_{}", name, method)];
+                if own.signature.params[1..] != inherited.params[..] || own.signature.return_type != inherited.return_type {
+                    panic!("Method '{}.{}' overrides '{}' from '{}' with a different signature", name, method, inherited, parent);
+                }
+                if rank(own.visibility) < rank(inherited_visibility) {
+                    panic!("Method '{}.{}' cannot be {} because it overrides a {} method", name, method, own.visibility, inherited_visibility);
+                }
+            }
+        }
+
+        symbols.classes.insert(name.to_string(), ClassInfo {
+            name: name.to_string(),
+            type_params: decl.type_params.clone(),
+            parent: decl.parent.clone(),
+            interfaces: decl.interfaces.clone(),
+            fields: field_map,
+            methods: method_names,
+            static_methods: static_method_names,
+            static_fields,
+            constructor,
+            is_record: decl.is_record,
+        });
+        self.classes.push(name.to_string());
+
+        // Type parameters belong to instances: static members cannot use them
+        let outer_type_params = std::mem::take(&mut self.type_params);
+        for f in &decl.fields {
+            self.type_params = if f.is_static { Vec::new() } else { decl.type_params.clone() };
+            self.check_type_exists(&f.ty, symbols);
+        }
+        self.type_params.clear();
+        let outer_class = self.current_class.replace(name.to_string());
+
+        // Static fields are initialized once, when execution reaches the class declaration. Every slot is
+        // zeroed first: a method called by an initializer may read a field whose initializer has not run yet.
+        let statics: Vec<&Field> = decl.fields.iter().filter(|f| f.is_static).collect();
+        for f in &statics {
+            self.emit_byte(OpCode::Push.into());
+            self.emit_int(0);
+            self.emit_store_var(symbols.classes[name].static_fields[&f.name].var.slot.clone());
+        }
+        self.pending_statics = statics.iter().map(|f| f.name.clone()).collect();
+        for f in &statics {
+            if let Some(init) = &f.initializer {
+                let var = symbols.classes[name].static_fields[&f.name].var.clone();
+                let value_ty = self.emit_expr(init, symbols);
+                self.expect_type(symbols, &value_ty, &f.ty, &format!("initializer of '{}.{}'", name, f.name));
+                self.emit_store_var(var.slot);
+            }
+            self.pending_statics.retain(|name| *name != f.name);
+        }
+
+        // Every method an interface requires must be declared or inherited with the same signature
+        for iface_name in &decl.interfaces {
+            let iface = symbols.interfaces.get(iface_name)
+                .unwrap_or_else(|| panic!("Class '{}' implements unknown interface '{}' (interfaces must be declared first)", name, iface_name));
+            for (method, required) in &iface.methods {
+                match symbols.method_signature(&this_ty, method) {
+                    None => panic!("Class '{}' does not implement '{}.{}'", name, iface_name, method),
+                    Some(found) if found != *required => panic!(
+                        "Method '{}.{}' does not match interface '{}': expected {}, found {}",
+                        name, method, iface_name, required, found
+                    ),
+                    Some(_) => {}
+                }
+                let owner = &symbols.find_method_owner(name, method).unwrap().name;
+                let visibility = symbols.functions[&format!("{}_{}", owner, method)].visibility;
+                if visibility != Visibility::Public {
+                    panic!("Method '{}.{}' implements interface '{}' and must be public", owner, method, iface_name);
+                }
+            }
+        }
+
+        // Emit methods
+        self.type_params = decl.type_params.clone();
+        for method in &methods {
+            self.emit_function(method, symbols);
+        }
+        self.type_params.clear();
+        for method in &decl.static_methods {
+            self.emit_function(method, symbols);
+        }
+        self.type_params = outer_type_params;
+        self.current_class = outer_class;
+    }
+
+    // A record gets a constructor taking its fields in order and, unless it declares them itself,
+    // equals (also used by '=='), hashCode and toString over all its fields
+    fn add_record_members(decl: &ClassDecl, methods: &mut Vec<FunctionDecl>, symbols: &mut SymbolTable) {
+        let name = &decl.name;
+        let this_ty = Type::Named(name.clone());
+        let fields: Vec<&Field> = decl.fields.iter().filter(|f| !f.is_static).collect();
+        let var = |n: &str| Expr::Variable(n.to_string());
+        let field_of = |obj: &str, f: &Field| Expr::GetField(Box::new(var(obj)), f.name.clone());
+        let param = |n: &str, ty: &Type| Param { name: n.to_string(), ty: ty.clone(), mutable: false, default: None, variadic: false };
+
+        let mut add = |method: &str, params: Vec<Param>, return_type: Type, body: Vec<Stmt>| {
+            let full_name = format!("{}_{}", name, method);
+            if methods.iter().any(|m| m.name == full_name) {
+                return; // Declared by the record itself
+            }
+            let mut all_params = vec![param("this", &this_ty)];
+            all_params.extend(params);
+            symbols.functions.insert(full_name.clone(), FunctionInfo {
+                name: full_name.clone(),
+                address: 0,
+                local_count: 0,
+                signature: Signature { params: all_params.iter().map(|p| p.ty.clone()).collect(), return_type: return_type.clone() },
+                visibility: Visibility::Public,
+                type_params: Vec::new(),
+                param_names: all_params.iter().map(|p| p.name.clone()).collect(),
+                defaults: all_params.iter().map(|p| p.default.clone()).collect(),
+                variadic: false,
+            });
+            methods.push(FunctionDecl { name: full_name, params: all_params, return_type, body });
+        };
+
+        // A field's initializer is the default of its constructor parameter
+        let field_param = |f: &Field| Param { default: f.initializer.clone(), ..param(&f.name, &f.ty) };
+        add(name, fields.iter().map(|f| field_param(f)).collect(), Type::Void,
+            fields.iter().map(|f| Stmt::FieldSet(Box::new(var("this")), f.name.clone(), var(&f.name))).collect());
+
+        // this.a == other.a ? (this.b == other.b ? ... : 0) : 0
+        let equal = fields.iter().rev().fold(None, |rest, f| {
+            let same = Expr::ValueEquals(Box::new(field_of("this", f)), Box::new(field_of("other", f)));
+            Some(match rest {
+                None => same,
+                Some(rest) => Expr::Conditional(Box::new(same), Box::new(rest), Box::new(Expr::Integer(0))),
+            })
+        });
+        add("equals", vec![param("other", &this_ty)], Type::Int, vec![Stmt::Return(equal.unwrap_or(Expr::Integer(1)))]);
+        add("operator==", vec![param("other", &this_ty)], Type::Int,
+            vec![Stmt::Return(Expr::MethodCall(Box::new(var("this")), "equals".to_string(), vec![var("other")]))]);
+
+        add("hashCode", Vec::new(), Type::Int, vec![Stmt::Return(Expr::ValueHash(fields.iter().map(|f| field_of("this", f)).collect()))]);
+
+        // Point(x=1, y=2)
+        let mut text = Expr::StringLiteral(format!("{}(", name.rsplit('.').next().unwrap()));
+        for (i, f) in fields.iter().enumerate() {
+            let label = format!("{}{}=", if i > 0 { ", " } else { "" }, f.name);
+            text = Expr::Binary(Box::new(text), Op::Add, Box::new(Expr::StringLiteral(label)));
+            text = Expr::Binary(Box::new(text), Op::Add, Box::new(Expr::ValueText(Box::new(field_of("this", f)))));
+        }
+        text = Expr::Binary(Box::new(text), Op::Add, Box::new(Expr::StringLiteral(")".to_string())));
+        add("toString", Vec::new(), Type::Str, vec![Stmt::Return(text)]);
+    }
+
+    fn emit_interface(&mut self, decl: &InterfaceDecl, symbols: &mut SymbolTable) {
+        if symbols.type_exists(&decl.name) {
+            panic!("Type '{}' is already declared", decl.name);
+        }
+        // Registered first so methods can take or return the interface itself
+        symbols.interfaces.insert(decl.name.clone(), InterfaceInfo { name: decl.name.clone(), methods: Vec::new() });
+
+        let mut methods: Vec<(String, Signature)> = Vec::new();
+        for m in &decl.methods {
+            if methods.iter().any(|(name, _)| *name == m.name) {
+                panic!("Method '{}' is declared twice in interface '{}'", m.name, decl.name);
+            }
+            for param in &m.params {
+                self.check_type_exists(&param.ty, symbols);
+            }
+            self.check_type_exists(&m.return_type, symbols);
+            let params = m.params.iter().map(|p| p.ty.clone()).collect();
+            methods.push((m.name.clone(), Signature { params, return_type: m.return_type.clone() }));
+        }
+        symbols.interfaces.get_mut(&decl.name).unwrap().methods = methods;
+    }
+
+    fn emit_enum(&mut self, decl: &EnumDecl, symbols: &mut SymbolTable) {
+        if symbols.type_exists(&decl.name) {
+            panic!("Type '{}' is already declared", decl.name);
+        }
+        let mut variants: Vec<VariantInfo> = Vec::new();
+        let mut next = 0;
+        for v in &decl.variants {
+            let value = v.value.unwrap_or(next);
+            if let Some(other) = variants.iter().find(|o| o.name == v.name || o.value == value) {
+                panic!("Enum variant '{}.{}' clashes with '{}.{}'", decl.name, v.name, decl.name, other.name);
+            }
+            let fields = v.fields.iter().map(|f| f.ty.clone()).collect();
+            variants.push(VariantInfo { name: v.name.clone(), value, fields });
+            next = value + 1;
+        }
+        symbols.enums.insert(decl.name.clone(), EnumInfo { name: decl.name.clone(), variants });
+
+        // Checked after registering so variants can refer to the enum itself
+        for v in &decl.variants {
+            for f in &v.fields {
+                self.check_type_exists(&f.ty, symbols);
+            }
+        }
+    }
+
+    // Emits a jump instruction with a placeholder offset. Returns the index of the placeholder.
+    fn emit_jump(&mut self, instruction: u8) -> usize {
+        self.emit_byte(instruction);
+        self.emit_byte(0xFF); // Placeholder (4 bytes)
+        self.emit_byte(0xFF);
+        self.emit_byte(0xFF);
+        self.emit_byte(0xFF);
+        self.code.len() - 4
+    }
+
+    // Emits an unconditional jump back to an already known bytecode index
+    fn emit_loop(&mut self, target: usize) {
+        self.emit_byte(OpCode::Jump.into());
+        let offset = (target as i32) - (self.code.len() as i32) - 4;
+        self.emit_int(offset);
+    }
+
+    // Finds the loop a break/continue refers to: the innermost one, or the one with a matching label
+    fn find_loop(&mut self, label: &Option<String>, keyword: &str) -> &mut LoopContext {
+        let is_continue = keyword == "continue";
+        let mut candidates = self.loops.iter_mut().rev().filter(|l| !(is_continue && l.is_switch)).peekable();
+        if candidates.peek().is_none() {
+            panic!("'{}' outside of a loop", keyword);
+        }
+        match label {
+            None => candidates.next().unwrap(),
+            Some(name) => candidates
+                .find(|l| l.label.as_deref() == Some(name.as_str()))
+                .unwrap_or_else(|| panic!("Undefined loop label '{}' in '{}'", name, keyword)),
+        }
+    }
+
+    fn patch_jump(&mut self, offset_index: usize) {
+        let jump_dist = (self.code.len() - offset_index - 4) as i32;
+        let bytes = jump_dist.to_le_bytes();
+        self.code[offset_index..offset_index + 4].copy_from_slice(&bytes);
+    }
+
+    fn add_constant(&mut self, s: String) -> usize {
+        if let Some(idx) = self.constants.iter().position(|c| *c == s) {
+            idx
+        } else {
+            self.constants.push(s);
+            self.constants.len() - 1
+        }
+    }
+
+    pub fn finalize(&mut self, symbols: &SymbolTable) {
+        // Top-level code has no frame of its own: its variables are globals
+        let handlers = std::mem::take(&mut self.handlers);
+        self.exception_tables.push(ExceptionTable { start: 0, end: self.code.len() as u32, frame_size: 0, handlers });
+
+        for (index, name) in &self.calls_to_patch {
+            let func_info = symbols.functions.get(name)
+                .unwrap_or_else(|| panic!("Undefined function: {}", name));
+
+            let bytes = (func_info.address as i32).to_le_bytes();
+            self.code[*index..*index + 4].copy_from_slice(&bytes);
+        }
+
+        // Flatten each class's method table: walk from the root class down so overrides replace inherited entries
+        for class_name in self.classes.clone() {
+            let chain: Vec<&ClassInfo> = symbols.ancestors(&class_name).collect();
+
+            let mut methods: Vec<(usize, u32)> = Vec::new();
+            for cls in chain.iter().rev() {
+                for method in &cls.methods {
+                    let info = &symbols.functions[&format!("{}_{}", cls.name, method)];
+                    if info.visibility == Visibility::Private {
+                        continue; // Called directly, never dispatched
+                    }
+                    let name_idx = self.add_constant(method.clone());
+                    let address = info.address;
+                    match methods.iter_mut().find(|(n, _)| *n == name_idx) {
+                        Some(entry) => entry.1 = address,
+                        None => methods.push((name_idx, address)),
+                    }
+                }
+            }
+
+            let cls = &symbols.classes[&class_name];
+            let entry = ClassEntry {
+                name: self.add_constant(class_name.clone()),
+                parent: cls.parent.clone().map(|p| self.add_constant(p)),
+                methods,
+            };
+            self.class_table.push(entry);
+        }
+    }
+
+    pub fn emit_stmt(&mut self, stmt: &Stmt, symbols: &mut SymbolTable) {
+        match stmt {
+            Stmt::VarDecl(name, ty, expr, is_final) => {
+                // The initializer is emitted first so it cannot see the variable it declares
+                let value_ty = self.emit_expr(expr, symbols); // Push value
+                if value_ty == Type::Void {
+                    panic!("Cannot initialize '{}' with a void value", name);
+                }
+                let ty = if *ty == Type::Infer {
+                    if value_ty == Type::Null || matches!(&value_ty, Type::Tuple(elems) if elems.contains(&Type::Null)) {
+                        panic!("Cannot infer the type of '{}' from null; declare it as a nullable type", name);
+                    }
+                    value_ty.clone()
+                } else {
+                    self.check_type_exists(ty, symbols);
+                    self.expect_type(symbols, &value_ty, ty, &format!("declaration of '{}'", name));
+                    ty.clone()
+                };
+                self.narrowed.retain(|(n, _)| n != name);
+                if ty.is_nullable() && !value_ty.is_nullable() {
+                    self.narrowed.push((name.clone(), ty.non_null()));
+                }
+
+                // Functions get a frame slot, top-level code a global one
+                let slot = symbols.declare(&self.global_name(name, symbols), ty, !is_final);
+                self.emit_store_var(slot);
+            }
+            Stmt::Destructure(names, expr, is_final) => {
+                let value_ty = self.emit_expr(expr, symbols); // Push tuple
+                let Type::Tuple(elems) = &value_ty else {
+                    if value_ty.is_nullable() { panic!("Value of type '{}' may be null: check it with '!= null' first", value_ty); }
+                    panic!("Cannot destructure a value of type '{}': expected a tuple", value_ty)
+                };
+                if elems.len() != names.len() {
+                    panic!("Cannot destructure '{}' into {} variables: it has {} elements", value_ty, names.len(), elems.len());
+                }
+                // Each element is read from a copy of the tuple ref; the last read consumes it
+                let last = names.iter().rposition(|n| n != "_");
+                for (i, (name, elem_ty)) in names.iter().zip(elems).enumerate() {
+                    if name == "_" { continue; }
+                    if *elem_ty == Type::Null {
+                        panic!("Cannot infer the type of '{}' from null; declare it as a nullable type", name);
+                    }
+                    if Some(i) != last { self.emit_byte(OpCode::Dup.into()); }
+                    self.emit_byte(OpCode::Push.into());
+                    self.emit_int(i as i32);
+                    self.emit_byte(OpCode::LoadArray.into());
+                    self.narrowed.retain(|(n, _)| n != name);
+                    let slot = symbols.declare(&self.global_name(name, symbols), elem_ty.clone(), !is_final);
+                    self.emit_store_var(slot);
+                }
+                if last.is_none() { self.emit_byte(OpCode::Pop.into()); }
+            }
+            Stmt::ConstDecl(name, ty, expr) => {
+                // No code: every use of the name is replaced by the folded value
+                let value = self.const_value(expr, "Const initializer", symbols);
+                let value_ty = if matches!(value, Expr::Integer(_)) { Type::Int } else { Type::Str };
+                if *ty != Type::Infer {
+                    self.expect_type(symbols, &value_ty, ty, &format!("declaration of '{}'", name));
+                }
+                symbols.declare_const(&self.global_name(name, symbols), value_ty, value);
+            }
+            Stmt::Assign(name, expr) => {
+                let var = self.lookup_var(name, symbols)
+                    .unwrap_or_else(|| panic!("Undefined variable: {}", name));
+                match var.slot {
+                    VarSlot::Const(_) => panic!("Cannot assign to const '{}'", name),
+                    _ if !var.mutable && self.captured.contains(name) && matches!(var.slot, VarSlot::Local(_)) => {
+                        panic!("Cannot assign to captured variable '{}': a lambda gets a copy of it", name)
+                    }
+                    _ if !var.mutable => panic!("Cannot assign to immutable variable '{}'", name),
+                    _ => {}
+                }
+                let (slot, ty) = (var.slot.clone(), var.ty.clone());
+                let value_ty = self.emit_expr(expr, symbols);
+                self.expect_type(symbols, &value_ty, &ty, &format!("assignment to '{}'", name));
+                self.emit_store_var(slot);
+                self.narrowed.retain(|(n, _)| n != name);
+                if ty.is_nullable() && !value_ty.is_nullable() {
+                    self.narrowed.push((name.clone(), ty.non_null()));
+                }
+            }
+            Stmt::ArraySet(name, index, value) => {
+                let array_ty = self.emit_load_var(name, symbols); // Load array ref
+                if symbols.method_signature(&array_ty.non_null(), "operator[]").is_some() {
+                    panic!("Cannot assign through '{}[...]': 'operator []' of '{}' only reads", name, array_ty);
+                }
+                let Type::Array(elem) = array_ty else { panic!("Cannot index '{}' of type '{}'", name, array_ty) };
+                let index_ty = self.emit_expr(index, symbols);
+                self.expect_type(symbols, &index_ty, &Type::Int, "array index");
+                let value_ty = self.emit_expr(value, symbols);
+                self.expect_type(symbols, &value_ty, &elem, &format!("element assignment to '{}'", name));
+                self.emit_byte(OpCode::StoreArray.into());
+            }
+            Stmt::Return(expr) => {
+                let value_ty = self.emit_expr(expr, symbols);
+                let return_type = self.current_function.as_ref()
+                    .map(|f| symbols.functions[f].signature.return_type.clone());
+                if return_type == Some(Type::Infer) {
+                    // First 'return' of a lambda: it fixes the lambda's return type
+                    let f = self.current_function.as_ref().unwrap();
+                    symbols.functions.get_mut(f).unwrap().signature.return_type = value_ty.clone();
+                } else if let Some(expected) = return_type.filter(|t| *t != Type::Void) {
+                    let function = self.current_function.as_ref().unwrap();
+                    let context = if function.starts_with("$lambda") { "return from lambda".to_string() } else { format!("return from '{}'", function) };
+                    self.expect_type(symbols, &value_ty, &expected, &context);
+                }
+                if self.tries.iter().any(|t| t.finally.is_some()) {
+                    // A try in the finally block may cut the operand stack: the value waits in a local
+                    symbols.push_scope();
+                    let temp = self.emit_temp("$return", &value_ty, symbols);
+                    self.emit_finally_exits(0, symbols);
+                    self.emit_load_slot(&temp);
+                    symbols.pop_scope();
+                }
+                self.emit_byte(OpCode::Return.into());
+            }
+            Stmt::Print(expr) => {
+                let ty = self.emit_expr(expr, symbols);
+                if ty == Type::Void {
+                    panic!("Cannot print a void value");
+                }
+                if self.has_method(&ty.non_null(), "toString", &[], &Type::Str, symbols) {
+                    self.emit_value_text(&ty, symbols); // Objects with a toString() print as its result
+                } else if ty.is_nullable() {
+                    // null prints as "null" rather than 0
+                    self.emit_byte(OpCode::Dup.into());
+                    self.emit_byte(OpCode::Push.into());
+                    self.emit_int(0);
+                    self.emit_byte(OpCode::Equal.into());
+                    let not_null = self.emit_jump(OpCode::JumpIfFalse.into());
+                    self.emit_byte(OpCode::Pop.into());
+                    let null_idx = self.add_constant("null".to_string());
+                    self.emit_byte(OpCode::LoadConst.into());
+                    self.emit_int(null_idx as i32);
+                    self.patch_jump(not_null);
+                }
+                self.emit_byte(OpCode::Print.into());
+            }
+            Stmt::Block(stmts) => {
+                symbols.push_scope();
+                for s in stmts {
+                    self.emit_stmt(s, symbols);
+                }
+                symbols.pop_scope();
+                // Checks on the block's own variables must not carry over to outer ones of the same name
+                for s in stmts {
+                    match s {
+                        Stmt::VarDecl(name, ..) => self.narrowed.retain(|(n, _)| n != name),
+                        Stmt::Destructure(names, ..) => self.narrowed.retain(|(n, _)| !names.contains(n)),
+                        _ => {}
+                    }
+                }
+            }
+            Stmt::If(cond, then_branch, else_branch) => {
+                let cond_ty = self.emit_expr(cond, symbols);
+                self.expect_type(symbols, &cond_ty, &Type::Int, "'if' condition");
+                let before = self.narrowed.clone();
+
+                // Jump to Else if false
+                let then_jump = self.emit_jump(OpCode::JumpIfFalse.into());
+
+                self.narrow_by_condition(cond, true, symbols);
+                self.emit_stmt(then_branch, symbols);
+                let after_then = std::mem::replace(&mut self.narrowed, before);
+
+                let else_jump = self.emit_jump(OpCode::Jump.into());
+
+                self.patch_jump(then_jump);
+
+                self.narrow_by_condition(cond, false, symbols);
+                if let Some(else_stmt) = else_branch {
+                    self.emit_stmt(else_stmt, symbols);
+                }
+
+                self.patch_jump(else_jump);
+
+                // What holds after the 'if' is what holds at the end of every branch that gets there
+                let then_exits = Self::always_exits(then_branch);
+                let else_exits = else_branch.as_ref().is_some_and(|e| Self::always_exits(e));
+                if else_exits && !then_exits {
+                    self.narrowed = after_then;
+                } else if !then_exits {
+                    self.narrowed.retain(|entry| after_then.contains(entry));
+                }
+            }
+            Stmt::While(cond, body) => {
+                let loop_start = self.code.len();
+                self.forget_assigned(std::slice::from_ref(body), symbols);
+                let before = self.narrowed.clone();
+
+                let cond_ty = self.emit_expr(cond, symbols);
+                self.expect_type(symbols, &cond_ty, &Type::Int, "'while' condition");
+                let exit_jump = self.emit_jump(OpCode::JumpIfFalse.into());
+                self.narrow_by_condition(cond, true, symbols);
+
+                self.loops.push(LoopContext {
+                    label: self.pending_label.take(),
+                    start: loop_start,
+                    break_jumps: Vec::new(),
+                    is_switch: false,
+                    try_depth: self.tries.len(),
+                });
+                self.emit_stmt(body, symbols);
+                self.emit_loop(loop_start);
+                self.narrowed = before;
+
+                self.patch_jump(exit_jump);
+
+                // Every 'break' lands right after the loop
+                let ctx = self.loops.pop().unwrap();
+                for jump in ctx.break_jumps {
+                    self.patch_jump(jump);
+                }
+            }
+            Stmt::Switch(subject, cases, default) => {
+                self.forget_assigned(std::slice::from_ref(stmt), symbols);
+                self.emit_switch(subject, cases, default, symbols);
+            }
+            Stmt::Labeled(label, body) => {
+                if self.loops.iter().any(|l| l.label.as_ref() == Some(label)) {
+                    panic!("Loop label '{}' is already in use by an enclosing loop", label);
+                }
+                self.pending_label = Some(label.clone());
+                self.emit_stmt(body, symbols);
+            }
+            Stmt::Break(label) => {
+                let depth = self.find_loop(label, "break").try_depth;
+                self.emit_finally_exits(depth, symbols);
+                let jump = self.emit_jump(OpCode::Jump.into());
+                self.find_loop(label, "break").break_jumps.push(jump);
+            }
+            Stmt::Continue(label) => {
+                let ctx = self.find_loop(label, "continue");
+                let (start, depth) = (ctx.start, ctx.try_depth);
+                self.emit_finally_exits(depth, symbols);
+                self.emit_loop(start);
+            }
+            Stmt::Try(body, catches, finally) => {
+                self.forget_assigned(std::slice::from_ref(stmt), symbols);
+                self.emit_try(body, catches, finally, symbols);
+            }
+            Stmt::Throw(Some(expr)) => {
+                let ty = self.emit_expr(expr, symbols);
+                if !symbols.is_assignable(&ty, &Type::Named("Exception".to_string())) {
+                    panic!("Only Exception objects can be thrown, found '{}'", ty);
+                }
+                self.emit_byte(OpCode::Throw.into());
+            }
+            Stmt::Throw(None) => {
+                let slot = self.tries.iter().rev().find_map(|t| t.caught.clone())
+                    .unwrap_or_else(|| panic!("A bare 'throw' is only allowed inside a catch block"));
+                self.emit_load_slot(&slot);
+                self.emit_byte(OpCode::Rethrow.into());
+            }
+            Stmt::Expression(expr) => {
+                self.emit_expr(expr, symbols);
+                // An expression used as a statement should have its result popped.
+                self.emit_byte(OpCode::Pop.into());
+            }
+            Stmt::Function(func) => {
+                self.emit_function(func, symbols);
+            }
+            Stmt::Class(decl) => {
+                self.emit_class(decl, symbols);
+            }
+            Stmt::Interface(decl) => {
+                self.emit_interface(decl, symbols);
+            }
+            Stmt::Enum(decl) => {
+                self.emit_enum(decl, symbols);
+            }
+            Stmt::FieldSet(obj, field, value) => {
+                if let Some(class_name) = self.static_receiver(obj, symbols) {
+                    if symbols.enums.contains_key(&class_name) {
+                        panic!("Cannot assign to enum variant '{}.{}'", class_name, field);
+                    }
+                    let var = self.resolve_static_field(&class_name, field, symbols).var;
+                    if !var.mutable {
+                        panic!("Cannot assign to final static field '{}.{}'", class_name, field);
+                    }
+                    let value_ty = self.emit_expr(value, symbols);
+                    self.expect_type(symbols, &value_ty, &var.ty, &format!("assignment to field '{}'", field));
+                    self.emit_store_var(var.slot);
+                    return;
+                }
+                let obj_ty = self.emit_expr(obj, symbols);   // Push object ref
+
+                // Resolve field index
+                let field_info = self.resolve_field(&obj_ty, field, symbols);
+                self.check_field_write(obj, field, &field_info);
+
+                let value_ty = self.emit_expr(value, symbols); // Push value to assign
+                self.expect_type(symbols, &value_ty, &field_info.ty, &format!("assignment to field '{}'", field));
+
+                self.emit_byte(OpCode::SetField.into());
+                self.emit_int(field_info.index as i32);
+            }
+        }
+    }
+
+    pub fn write_file(&self, path: &str) -> std::io::Result<()> {
+        let file = File::create(path)?;
+        let mut writer = BufWriter::new(file);
+        writer.write_all(b"AMBR")?; // Magic
+        writer.write_all(&3u16.to_le_bytes())?; // Version
+        writer.write_all(&0u32.to_le_bytes())?; // Entry point placeholder
+
+        // Write Constant Pool
+        writer.write_all(&(self.constants.len() as u32).to_le_bytes())?;
+        for s in &self.constants {
             writer.write_all(&(s.len() as u32).to_le_bytes())?;
             writer.write_all(s.as_bytes())?;
         }
 
+        // Write Class Table (parent is -1 for root classes)
+        writer.write_all(&(self.class_table.len() as u32).to_le_bytes())?;
+        for cls in &self.class_table {
+            writer.write_all(&(cls.name as u32).to_le_bytes())?;
+            writer.write_all(&cls.parent.map_or(-1, |p| p as i32).to_le_bytes())?;
+            writer.write_all(&(cls.methods.len() as u32).to_le_bytes())?;
+            for (name, address) in &cls.methods {
+                writer.write_all(&(*name as u32).to_le_bytes())?;
+                writer.write_all(&address.to_le_bytes())?;
+            }
+        }
+
+        // Write Exception Tables (class -1 catches anything)
+        writer.write_all(&(self.exception_tables.len() as u32).to_le_bytes())?;
+        for table in &self.exception_tables {
+            writer.write_all(&table.start.to_le_bytes())?;
+            writer.write_all(&table.end.to_le_bytes())?;
+            writer.write_all(&table.frame_size.to_le_bytes())?;
+            writer.write_all(&(table.handlers.len() as u32).to_le_bytes())?;
+            for handler in &table.handlers {
+                writer.write_all(&handler.start.to_le_bytes())?;
+                writer.write_all(&handler.end.to_le_bytes())?;
+                writer.write_all(&handler.target.to_le_bytes())?;
+                writer.write_all(&handler.class.to_le_bytes())?;
+            }
+        }
+
         writer.write_all(&(self.code.len() as u32).to_le_bytes())?;
         writer.write_all(&self.code)?;
         Ok(())
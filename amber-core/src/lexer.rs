@@ -2,16 +2,18 @@
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Token {
-    Var, Mut, Func, Class, Return, Print,
+    Var, Mut, Const, Final, Static, Public, Private, Protected, Func, Class, Extends, Interface, Implements, Enum, Match, Super, Return, Print,
     Int, Void, String, New, // Types & Keywords
-    If, Else, While,
+    If, Else, While, Break, Continue, Switch, Case, Default,
+    Try, Catch, Finally, Throw, Null, Is, As, Operator, Record, Import,
     Identifier(String),
+    Unknown(char), // A character the language does not use, kept only after 'operator' to report it
     Number(i64),
     StringLit(String),
-    Equals, Plus, Minus, Star, Slash, Comma, Dot, LessThan,
+    Equals, EqualEqual, BangEqual, FatArrow, Arrow, Question, QuestionDot, QuestionQuestion, Plus, Minus, Star, Slash, Comma, Dot, Ellipsis, Colon, LessThan, GreaterThan,
     LParen, RParen, LBrace, RBrace, LBracket, RBracket,
     Newline,
-    EOF,
+    Eof,
 }
 
 pub struct Lexer {
@@ -31,8 +33,12 @@ impl Lexer {
             match c {
                 ' ' | '\r' | '\t' => { self.pos += 1; }
                 '\n' => { tokens.push(Token::Newline); self.pos += 1; }
+                '=' if self.peek_char(1) == Some('=') => { tokens.push(Token::EqualEqual); self.pos += 2; }
+                '=' if self.peek_char(1) == Some('>') => { tokens.push(Token::FatArrow); self.pos += 2; }
                 '=' => { tokens.push(Token::Equals); self.pos += 1; }
+                '!' if self.peek_char(1) == Some('=') => { tokens.push(Token::BangEqual); self.pos += 2; }
                 '+' => { tokens.push(Token::Plus); self.pos += 1; }
+                '-' if self.peek_char(1) == Some('>') => { tokens.push(Token::Arrow); self.pos += 2; }
                 '-' => { tokens.push(Token::Minus); self.pos += 1; }
                 '*' => { tokens.push(Token::Star); self.pos += 1; }
                 '/' => { 
@@ -45,7 +51,13 @@ impl Lexer {
                     }
                 }
                 '<' => { tokens.push(Token::LessThan); self.pos += 1; }
+                '>' => { tokens.push(Token::GreaterThan); self.pos += 1; }
+                '.' if self.peek_char(1) == Some('.') && self.peek_char(2) == Some('.') => { tokens.push(Token::Ellipsis); self.pos += 3; }
                 '.' => { tokens.push(Token::Dot); self.pos += 1; }
+                '?' if self.peek_char(1) == Some('.') => { tokens.push(Token::QuestionDot); self.pos += 2; }
+                '?' if self.peek_char(1) == Some('?') => { tokens.push(Token::QuestionQuestion); self.pos += 2; }
+                '?' => { tokens.push(Token::Question); self.pos += 1; }
+                ':' => { tokens.push(Token::Colon); self.pos += 1; }
                 ',' => { tokens.push(Token::Comma); self.pos += 1; }
                 '(' => { tokens.push(Token::LParen); self.pos += 1; }
                 ')' => { tokens.push(Token::RParen); self.pos += 1; }
@@ -56,10 +68,11 @@ impl Lexer {
                 'a'..='z' | 'A'..='Z' | '_' => tokens.push(self.read_identifier()),
                 '0'..='9' => tokens.push(self.read_number()),
                 '"' => tokens.push(self.read_string()),
+                _ if tokens.last() == Some(&Token::Operator) => { tokens.push(Token::Unknown(c)); self.pos += 1; }
                 _ => { self.pos += 1; } // Skip unknowns
             }
         }
-        tokens.push(Token::EOF);
+        tokens.push(Token::Eof);
         tokens
     }
 
@@ -76,20 +89,47 @@ impl Lexer {
             "String" => Token::String,
             "new" => Token::New,
             "mut" => Token::Mut,
+            "const" => Token::Const,
+            "final" => Token::Final,
             "func" => Token::Func,
             "class" => Token::Class,
+            "extends" => Token::Extends,
+            "static" => Token::Static,
+            "public" => Token::Public,
+            "private" => Token::Private,
+            "protected" => Token::Protected,
+            "interface" => Token::Interface,
+            "enum" => Token::Enum,
+            "match" => Token::Match,
+            "implements" => Token::Implements,
+            "super" => Token::Super,
             "return" => Token::Return,
             "print" => Token::Print,
             "if" => Token::If,
             "else" => Token::Else,
             "while" => Token::While,
+            "break" => Token::Break,
+            "switch" => Token::Switch,
+            "case" => Token::Case,
+            "default" => Token::Default,
+            "continue" => Token::Continue,
+            "try" => Token::Try,
+            "catch" => Token::Catch,
+            "finally" => Token::Finally,
+            "throw" => Token::Throw,
+            "null" => Token::Null,
+            "is" => Token::Is,
+            "as" => Token::As,
+            "operator" => Token::Operator,
+            "record" => Token::Record,
+            "import" => Token::Import,
             _ => Token::Identifier(text),
         }
     }
 
     fn read_number(&mut self) -> Token {
         let start = self.pos;
-        while self.pos < self.input.len() && self.input[self.pos].is_digit(10) {
+        while self.pos < self.input.len() && self.input[self.pos].is_ascii_digit() {
             self.pos += 1;
         }
         let text: String = self.input[start..self.pos].iter().collect();
@@ -106,4 +146,9 @@ impl Lexer {
         if self.pos < self.input.len() { self.pos += 1; } // Skip closing quote
         Token::StringLit(text)
     }
+
+    // Looks ahead without consuming, for two-character operators
+    fn peek_char(&self, offset: usize) -> Option<char> {
+        self.input.get(self.pos + offset).copied()
+    }
 }
@@ -3,43 +3,73 @@ mod parser;
 mod semant;
 mod codegen;
 mod ast;
+mod prelude;
+mod modules;
 use codegen::bytecode::OpCode;
 
 use std::env;
-use std::fs;
+use std::path::{Path, PathBuf};
 use lexer::Lexer;
 use parser::Parser;
 use semant::SymbolTable;
 use codegen::emitter::Emitter;
+use modules::Loader;
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
-        eprintln!("Usage: ambc <file.amb>");
-        std::process::exit(1);
+    // ambc [-I dir]... <file.amb>
+    let mut args = env::args().skip(1);
+    let mut include_dirs = Vec::new();
+    let mut filename = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-I" => include_dirs.push(PathBuf::from(args.next().unwrap_or_else(|| usage()))),
+            _ if filename.is_none() => filename = Some(arg),
+            _ => usage(),
+        }
     }
+    let filename = filename.unwrap_or_else(|| usage());
+    let main_path = Path::new(&filename);
 
-    let filename = &args[1];
-    let source = fs::read_to_string(filename).expect("Failed to read source file");
-    
-    // 1. Tokenize
-    let mut lexer = Lexer::new(source);
-    let tokens = lexer.tokenize();
+    // 1. Read the program: imports are looked up next to the main file, then in the -I directories,
+    // then in AMBER_PATH, then in the stdlib installed next to the compiler (bin/../stdlib)
+    let mut search_path = vec![main_path.parent().map_or_else(|| PathBuf::from("."), Path::to_path_buf)];
+    search_path.extend(include_dirs);
+    if let Some(paths) = env::var_os("AMBER_PATH") {
+        search_path.extend(env::split_paths(&paths));
+    }
+    if let Some(bin_dir) = env::current_exe().ok().and_then(|exe| exe.parent().map(Path::to_path_buf)) {
+        search_path.push(bin_dir.join("..").join("stdlib"));
+    }
+    let modules = Loader::load(main_path, search_path);
 
-    // 2. Parse & Semantic Analysis
+    // 2. Parse & Emit each module after the ones it imports (the prelude's classes come first, so programs
+    // can extend them). A module is fully parsed before it is emitted, so its functions can be called
+    // ahead of their declaration.
     let mut symbols = SymbolTable::new();
-    let mut parser = Parser::new(tokens);
-    let ast = parser.parse(&mut symbols);
-
-    // 3. Emit
     let mut emitter = Emitter::new();
-    for stmt in ast {
+    for stmt in Parser::new(Lexer::new(prelude::SOURCE.to_string()).tokenize()).parse(&mut symbols) {
         emitter.emit_stmt(&stmt, &mut symbols);
     }
+    for mut module in modules {
+        module.parser.scope = module.scope(&symbols);
+        let ast = module.parser.parse(&mut symbols);
+        let mut scope = std::mem::take(&mut module.parser.scope);
+        modules::declare_top_level(&module, &ast, &mut scope, &mut symbols);
+        emitter.scope = scope;
+        for stmt in ast {
+            emitter.emit_stmt(&stmt, &mut symbols);
+        }
+    }
     emitter.emit_byte(OpCode::Halt.into());
     emitter.finalize(&symbols); // Patch function calls
 
+    // 3. One linked .amc holds every module
     let output_path = filename.replace(".amb", ".amc");
     emitter.write_file(&output_path).expect("Failed to write file");
     println!("Amberlink: Compiled {} to {}", filename, output_path);
 }
+
+fn usage() -> ! {
+    eprintln!("Usage: ambc [-I <dir>]... <file.amb>");
+    std::process::exit(1);
+}
@@ -0,0 +1,159 @@
+// amber-core/src/modules.rs
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::ast::{Stmt, Visibility};
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::semant::{ModuleInfo, ModuleScope, SymbolTable};
+
+// One source file of the program, read and positioned after its imports
+pub struct Module {
+    pub name: String,   // geometry.shapes for geometry/shapes.amb; the file name for the main file
+    pub is_main: bool,  // The main file's declarations keep their plain names
+    pub parser: Parser,
+    imports: Vec<Import>,
+    path: PathBuf,      // Canonical path of the file: a module is loaded once however it is reached
+}
+
+// import geometry (everything it declares) or import geometry.Point (just Point)
+struct Import {
+    module: String,
+    name: Option<String>,
+}
+
+// Reads the main file and everything it imports, transitively. 'import a.b' looks for a/b.amb in each
+// directory of the search path in turn; if there is none, it imports the declaration 'b' of module 'a'.
+pub struct Loader {
+    search_path: Vec<PathBuf>,
+    modules: Vec<Module>,          // Every module after the ones it imports; the main file last
+    visiting: Vec<(String, PathBuf)>, // Modules being loaded, outermost first, to report import cycles
+}
+
+impl Loader {
+    pub fn load(main: &Path, search_path: Vec<PathBuf>) -> Vec<Module> {
+        let mut loader = Loader { search_path, modules: Vec::new(), visiting: Vec::new() };
+        let name = main.file_stem().map_or(String::new(), |s| s.to_string_lossy().into_owned());
+        loader.load_module(name, main.to_path_buf(), true);
+        loader.modules
+    }
+
+    fn load_module(&mut self, name: String, path: PathBuf, is_main: bool) {
+        let id = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+        if let Some(start) = self.visiting.iter().position(|(_, p)| *p == id) {
+            let mut chain: Vec<&str> = self.visiting[start..].iter().map(|(n, _)| n.as_str()).collect();
+            chain.push(&self.visiting[start].0);
+            panic!("Import cycle: {}", chain.join(" -> "));
+        }
+        if let Some(loaded) = self.modules.iter().find(|m| m.path == id) {
+            if loaded.name != name {
+                panic!("Modules '{}' and '{}' are the same file: {}", loaded.name, name, id.display());
+            }
+            return; // Already loaded through another import
+        }
+        if let Some(other) = self.modules.iter().find(|m| m.name == name && !m.is_main) {
+            panic!("Module '{}' is found both at {} and {}", name, other.path.display(), id.display());
+        }
+        let source = match fs::read_to_string(&path) {
+            Ok(source) => source,
+            Err(e) if is_main => panic!("Failed to read source file {}: {}", path.display(), e),
+            Err(e) => panic!("Failed to read module '{}' ({}): {}", name, path.display(), e),
+        };
+        let mut parser = Parser::new(Lexer::new(source).tokenize());
+
+        self.visiting.push((name.clone(), id.clone()));
+        let mut imports = Vec::new();
+        for import_path in parser.parse_imports() {
+            let (module, module_path, imported_name) = self.find(&import_path);
+            self.load_module(module.clone(), module_path, false);
+            imports.push(Import { module, name: imported_name });
+        }
+        self.visiting.pop();
+        self.modules.push(Module { name, is_main, parser, imports, path: id });
+    }
+
+    // The module an import path names, where its file is, and the single declaration it imports, if any
+    fn find(&self, path: &[String]) -> (String, PathBuf, Option<String>) {
+        for dir in &self.search_path {
+            let file = dir.join(path.join("/")).with_extension("amb");
+            if file.is_file() {
+                return (path.join("."), file, None);
+            }
+            if let Some((name, module)) = path.split_last().filter(|(_, module)| !module.is_empty()) {
+                let file = dir.join(module.join("/")).with_extension("amb");
+                if file.is_file() {
+                    return (module.join("."), file, Some(name.clone()));
+                }
+            }
+        }
+        let dirs: Vec<String> = self.search_path.iter().map(|d| d.display().to_string()).collect();
+        panic!("Module '{}' not found in the search path: {}", path.join("."), dirs.join(", "));
+    }
+}
+
+impl Module {
+    // What this file can name without qualification before its body is parsed: its imports and its own
+    // types (its functions and globals are added by declare_top_level once they are parsed)
+    pub fn scope(&self, symbols: &SymbolTable) -> ModuleScope {
+        let mut scope = ModuleScope { prefix: (!self.is_main).then(|| self.name.clone()), ..ModuleScope::default() };
+        for import in &self.imports {
+            let info = &symbols.modules[&import.module];
+            match &import.name {
+                None => {
+                    let alias = import.module.rsplit('.').next().unwrap();
+                    scope.modules.insert(alias.to_string(), import.module.clone());
+                    for (name, qualified) in &info.exports {
+                        scope.import(name, qualified, &import.module);
+                    }
+                }
+                Some(name) if info.private.contains(name) => {
+                    panic!("Cannot import '{}': it is private to module '{}'", name, import.module);
+                }
+                Some(name) => {
+                    let qualified = info.exports.get(name)
+                        .unwrap_or_else(|| panic!("Module '{}' has no top-level declaration '{}'", import.module, name));
+                    scope.import(name, qualified, &import.module);
+                }
+            }
+        }
+        for name in self.parser.declared_types() {
+            scope.declare(&name);
+        }
+        scope
+    }
+}
+
+// Adds a parsed module's functions and globals to its scope and, for an imported module, records
+// what it exports. Private functions stay usable inside their own file only.
+pub fn declare_top_level(module: &Module, ast: &[Stmt], scope: &mut ModuleScope, symbols: &mut SymbolTable) {
+    let mut exports = HashMap::new();
+    let mut private = Vec::new();
+    for stmt in ast {
+        let names = match stmt {
+            Stmt::Function(f) => vec![&f.name],
+            Stmt::Class(c) => vec![&c.name],
+            Stmt::Interface(i) => vec![&i.name],
+            Stmt::Enum(e) => vec![&e.name],
+            Stmt::VarDecl(name, ..) | Stmt::ConstDecl(name, ..) => vec![name],
+            Stmt::Destructure(names, ..) => names.iter().filter(|n| *n != "_").collect(),
+            _ => Vec::new(),
+        };
+        for name in names {
+            // Declarations were qualified by the parser, variables are qualified by the Emitter
+            let plain = match &scope.prefix {
+                Some(prefix) => name.strip_prefix(&format!("{}.", prefix)).unwrap_or(name).to_string(),
+                None => name.clone(),
+            };
+            scope.declare(&plain);
+            let is_private = matches!(stmt, Stmt::Function(_)) && symbols.functions[name].visibility == Visibility::Private;
+            if is_private {
+                private.push(plain);
+            } else {
+                exports.insert(plain.clone(), scope.qualify(&plain));
+            }
+        }
+    }
+    if !module.is_main {
+        symbols.modules.insert(module.name.clone(), ModuleInfo { exports, private });
+    }
+}
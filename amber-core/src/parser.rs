@@ -1,16 +1,77 @@
 // amber-core/src/parser.rs
 use crate::lexer::Token;
-use crate::semant::SymbolTable;
-use crate::ast::{Stmt, Expr, Op};
+use crate::semant::{FunctionInfo, ModuleScope, Signature, SymbolTable};
+use crate::ast::{Stmt, Expr, Op, Param, Field, Type, Visibility, FunctionDecl, ClassDecl, InterfaceDecl, EnumDecl, VariantDecl, MethodSig, MatchArm, Pattern, CatchClause};
 
 pub struct Parser {
     tokens: Vec<Token>,
     pos: usize,
+    type_params: Vec<String>, // Type parameters in scope: names in types parse as Type::Param
+    pub scope: ModuleScope, // Qualifies declared names and resolves type names of other modules
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
-        Self { tokens, pos: 0 }
+        Self { tokens, pos: 0, type_params: Vec::new(), scope: ModuleScope::default() }
+    }
+
+    // The 'import a.b.c' lines a file starts with, as paths
+    pub fn parse_imports(&mut self) -> Vec<Vec<String>> {
+        let mut imports = Vec::new();
+        loop {
+            match self.peek() {
+                Token::Newline => { self.advance(); }
+                Token::Import => {
+                    self.advance(); // consume 'import'
+                    let mut path = Vec::new();
+                    loop {
+                        match self.advance() { Token::Identifier(name) => path.push(name), tok => panic!("Expected module name after 'import', found {:?}", tok) }
+                        if self.peek() == Token::Dot { self.advance(); } else { break; }
+                    }
+                    if !matches!(self.peek(), Token::Newline | Token::Eof) { panic!("Expected end of line after 'import {}'", path.join(".")); }
+                    imports.push(path);
+                }
+                _ => return imports,
+            }
+        }
+    }
+
+    // Classes, records, interfaces and enums declared at the top level, before parsing, so that
+    // types can name them ahead of their declaration
+    pub fn declared_types(&self) -> Vec<String> {
+        let mut depth = 0;
+        let mut names = Vec::new();
+        for (i, tok) in self.tokens.iter().enumerate().skip(self.pos) {
+            match tok {
+                Token::LBrace => depth += 1,
+                Token::RBrace => depth -= 1,
+                Token::Class | Token::Record | Token::Interface | Token::Enum if depth == 0 => {
+                    if let Some(Token::Identifier(name)) = self.tokens.get(i + 1) { names.push(name.clone()); }
+                }
+                _ => {}
+            }
+        }
+        names
+    }
+
+    // A class, interface or enum name as written, made to name the declaration it refers to
+    fn type_name(&self, name: String) -> String {
+        self.scope.resolve(&name).map_or(name, str::to_string)
+    }
+
+    // Like type_name, but also accepts alias.Type for a type of an imported module (the alias already consumed)
+    fn parse_type_name(&mut self, name: String) -> String {
+        if self.is_module_type_at(0, &name) {
+            self.advance(); // consume '.'
+            let Token::Identifier(member) = self.advance() else { unreachable!() };
+            return format!("{}.{}", self.scope.modules[&name], member);
+        }
+        self.type_name(name)
+    }
+
+    // Whether '.Type' follows, n tokens ahead, a name that is a module alias
+    fn is_module_type_at(&self, n: usize, name: &str) -> bool {
+        self.scope.modules.contains_key(name) && self.peek_n(n) == Token::Dot && matches!(self.peek_n(n + 1), Token::Identifier(_))
     }
 
     pub fn parse(&mut self, symbols: &mut SymbolTable) -> Vec<Stmt> {
@@ -27,24 +88,82 @@ impl Parser {
 
     fn parse_statement(&mut self, symbols: &mut SymbolTable) -> Stmt {
         match self.peek() {
-            Token::Var => self.parse_declaration(),
-            Token::Int | Token::Void | Token::String => {
+            Token::Var => self.parse_declaration(false),
+            Token::Final => {
+                self.advance(); // consume 'final'
+                self.parse_declaration(true)
+            }
+            Token::Const => {
+                self.advance(); // consume 'const'
+                match self.parse_declaration(true) {
+                    Stmt::VarDecl(name, ty, value, _) => Stmt::ConstDecl(name, ty, value),
+                    _ => panic!("const cannot destructure a tuple: use 'final var (...)' instead"),
+                }
+            }
+            Token::Int | Token::Void | Token::String | Token::Func => {
                 // Lookahead to distinguish Variable Declaration vs Function Definition
                 // int x = 5;       (Type -> Identifier -> Equals)
-                // int x() { ... }  (Type -> Identifier -> LParen)
-                if matches!(self.peek_n(1), Token::Identifier(_)) && self.peek_n(2) == Token::LParen {
-                    self.parse_function(symbols)
+                // int x() { ... }  (Type -> Identifier -> LParen, or '<' for a generic function)
+                if self.is_function_start() {
+                    self.parse_function(symbols, Visibility::Public)
                 } else {
-                    self.parse_declaration()
+                    self.parse_declaration(false)
+                }
+            }
+            Token::Public | Token::Private | Token::Protected => {
+                // Only functions take a visibility at the top level
+                let visibility = self.parse_visibility();
+                if visibility == Visibility::Protected { panic!("'protected' only applies to class members"); }
+                if !self.is_function_start() {
+                    panic!("Expected a function after '{}'", visibility);
                 }
+                self.parse_function(symbols, visibility)
             }
+            Token::Import => panic!("'import' must come before any other statement"),
             Token::If => self.parse_if(symbols),
             Token::While => self.parse_while(symbols),
+            Token::Switch => self.parse_switch(symbols),
+            Token::Try => self.parse_try(symbols),
+            Token::Throw => {
+                self.advance(); // skip 'throw'
+                // A bare 'throw' (end of line or block) rethrows the exception being caught
+                match self.peek() {
+                    Token::Newline | Token::RBrace | Token::Eof => Stmt::Throw(None),
+                    _ => Stmt::Throw(Some(self.parse_expr())),
+                }
+            }
+            Token::Break => {
+                self.advance(); // skip 'break'
+                Stmt::Break(self.parse_jump_label())
+            }
+            Token::Continue => {
+                self.advance(); // skip 'continue'
+                Stmt::Continue(self.parse_jump_label())
+            }
             Token::LBrace => self.parse_block(symbols),
             Token::Class => self.parse_class_decl(symbols),
+            Token::Record => self.parse_record_decl(symbols),
+            Token::Interface => self.parse_interface_decl(),
+            Token::Enum => self.parse_enum_decl(),
             Token::Return => self.parse_return(),
             Token::Print => self.parse_print(),
-            // Token::Func is deprecated in favor of C-style types
+            // 'func' no longer declares functions; it only starts a function type (handled above)
+            Token::Identifier(label) if self.peek_n(1) == Token::Colon => {
+                // Labeled loop: outer: while cond { ... }
+                self.advance(); // consume label
+                self.advance(); // consume ':'
+                while self.peek() == Token::Newline { self.advance(); }
+                if self.peek() != Token::While { panic!("Label '{}' must be followed by a loop", label); }
+                Stmt::Labeled(label, Box::new(self.parse_statement(symbols)))
+            }
+            Token::Identifier(_) | Token::LParen if self.type_len_at(0) > 0 && matches!(self.peek_n(self.type_len_at(0)), Token::Identifier(_)) => {
+                // Class- or tuple-typed declaration or function: Point p = ... / Box<int> b = ... / (int, int) pair() { ... }
+                if self.is_function_start() {
+                    self.parse_function(symbols, Visibility::Public)
+                } else {
+                    self.parse_declaration(false)
+                }
+            }
             Token::Identifier(_) => {
                 // Parse as expression first to handle L-values (Variable or ArrayAccess)
                 let expr = self.parse_expr();
@@ -69,17 +188,64 @@ impl Parser {
     // --- Expression Parsing (Recursive Descent) ---
 
     fn parse_expr(&mut self) -> Expr {
-        self.parse_comparison()
+        self.parse_conditional()
+    }
+
+    // Handles cond ? a : b (loosest, right-associative): a ? b : c ? d : e is a ? b : (c ? d : e)
+    fn parse_conditional(&mut self) -> Expr {
+        let cond = self.parse_coalesce();
+        if self.peek() != Token::Question {
+            return cond;
+        }
+        self.advance(); // consume '?'
+        let then_value = self.parse_conditional();
+        if self.advance() != Token::Colon { panic!("Expected ':' in conditional expression"); }
+        let else_value = self.parse_conditional();
+        Expr::Conditional(Box::new(cond), Box::new(then_value), Box::new(else_value))
+    }
+
+    // Handles ?? (loosest, right-associative): a ?? b ?? c is a ?? (b ?? c)
+    fn parse_coalesce(&mut self) -> Expr {
+        let expr = self.parse_equality();
+        if self.peek() == Token::QuestionQuestion {
+            self.advance(); // consume '??'
+            let default = self.parse_coalesce();
+            return Expr::Coalesce(Box::new(expr), Box::new(default));
+        }
+        expr
+    }
+
+    // Handles == and != (binds looser than <)
+    fn parse_equality(&mut self) -> Expr {
+        let mut expr = self.parse_comparison();
+        while matches!(self.peek(), Token::EqualEqual | Token::BangEqual) {
+            let op = match self.advance() {
+                Token::EqualEqual => Op::Equal,
+                Token::BangEqual => Op::NotEqual,
+                _ => unreachable!(),
+            };
+            let right = self.parse_comparison();
+            expr = Expr::Binary(Box::new(expr), op, Box::new(right));
+        }
+        expr
     }
 
     fn parse_comparison(&mut self) -> Expr {
         let mut expr = self.parse_term();
-        while matches!(self.peek(), Token::LessThan) {
-            self.advance(); // consume '<'
-            let right = self.parse_term();
-            expr = Expr::Binary(Box::new(expr), Op::LessThan, Box::new(right));
+        loop {
+            match self.peek() {
+                Token::LessThan => {
+                    self.advance(); // consume '<'
+                    let right = self.parse_term();
+                    expr = Expr::Binary(Box::new(expr), Op::LessThan, Box::new(right));
+                }
+                Token::Is => {
+                    self.advance(); // consume 'is'
+                    expr = Expr::InstanceOf(Box::new(expr), self.parse_class_target("is", false));
+                }
+                _ => return expr,
+            }
         }
-        expr
     }
 
     // Handles + and -
@@ -99,48 +265,183 @@ impl Parser {
 
     // Handles * and /
     fn parse_factor(&mut self) -> Expr {
-        let mut expr = self.parse_primary();
+        let mut expr = self.parse_cast();
         while matches!(self.peek(), Token::Star | Token::Slash) {
             let op = match self.advance() {
                 Token::Star => Op::Mul,
                 Token::Slash => Op::Div,
                 _ => unreachable!(),
             };
-            let right = self.parse_primary();
+            let right = self.parse_cast();
             expr = Expr::Binary(Box::new(expr), op, Box::new(right));
         }
         expr
     }
 
+    // Handles 'as': shape as Circle, shape as Circle?
+    fn parse_cast(&mut self) -> Expr {
+        let mut expr = self.parse_postfix();
+        while self.peek() == Token::As {
+            self.advance(); // consume 'as'
+            expr = Expr::Cast(Box::new(expr), self.parse_class_target("as", true));
+        }
+        expr
+    }
+
+    // Member access chains: obj.field, obj.method(args), a.b.c()
+    fn parse_postfix(&mut self) -> Expr {
+        let mut expr = self.parse_primary();
+        loop {
+            if self.peek() == Token::LParen && matches!(expr, Expr::Call(..) | Expr::MethodCall(..) | Expr::SafeMethodCall(..) | Expr::CallValue(..) | Expr::Lambda(..)) {
+                // Calling the function value an expression produced: make(1)(2)
+                self.advance(); // consume '('
+                let mut args = Vec::new();
+                if self.peek() != Token::RParen {
+                    loop {
+                        args.push(self.parse_arg());
+                        if self.peek() == Token::Comma { self.advance(); } else { break; }
+                    }
+                }
+                if self.advance() != Token::RParen { panic!("Expected ')' after arguments"); }
+                expr = Expr::CallValue(Box::new(expr), args);
+                continue;
+            }
+            // obj?.member skips the access when obj is null
+            let safe = match self.peek() {
+                Token::Dot => false,
+                Token::QuestionDot => true,
+                _ => break,
+            };
+            self.advance(); // consume '.' or '?.'
+            let member = match self.advance() {
+                Token::Identifier(f) => f,
+                Token::Number(i) if !safe => {
+                    expr = Expr::TupleElement(Box::new(expr), i as usize);
+                    continue;
+                }
+                _ => panic!("Expected member name"),
+            };
+
+            if self.peek() == Token::LParen {
+                self.advance(); // consume '('
+                let mut args = Vec::new();
+                if self.peek() != Token::RParen {
+                    loop {
+                        args.push(self.parse_arg());
+                        if self.peek() == Token::Comma { self.advance(); } else { break; }
+                    }
+                }
+                if self.advance() != Token::RParen { panic!("Expected ')' after arguments"); }
+                expr = if safe { Expr::SafeMethodCall(Box::new(expr), member, args) } else { Expr::MethodCall(Box::new(expr), member, args) };
+            } else {
+                expr = if safe { Expr::SafeGetField(Box::new(expr), member) } else { Expr::GetField(Box::new(expr), member) };
+            }
+        }
+        expr
+    }
+
     fn parse_primary(&mut self) -> Expr {
         match self.advance() {
             Token::Number(val) => Expr::Integer(val as i32),
             Token::New => {
-                // new int[size] OR new MyClass()
-                let type_token = self.advance();
+                // new int[size] OR new MyClass() OR new Box<int>() (type arguments inferred when omitted)
+                if self.peek() == Token::LParen {
+                    // new (int, String)[size]: an array of tuples
+                    let elem = self.parse_type();
+                    if self.advance() != Token::LBracket { panic!("Expected '[' after tuple type in 'new'"); }
+                    let size = self.parse_expr();
+                    if self.advance() != Token::RBracket { panic!("Expected ']' after size"); }
+                    return Expr::NewArray(elem, Box::new(size));
+                }
+                let type_token = match self.advance() {
+                    Token::Identifier(name) if !self.type_params.contains(&name) => Token::Identifier(self.parse_type_name(name)),
+                    tok => tok,
+                };
+                let type_args = if matches!(type_token, Token::Identifier(_)) && self.peek() == Token::LessThan {
+                    self.parse_type_args()
+                } else {
+                    Vec::new()
+                };
+                // new Point?[n]: an array of nullable elements
+                let nullable = self.peek() == Token::Question && self.peek_n(1) == Token::LBracket;
+                if nullable { self.advance(); }
                 match type_token {
-                    Token::Int | Token::String => {
-                        if self.advance() != Token::LBracket { panic!("Expected '[' after type"); }
+                    Token::Int | Token::String | Token::Identifier(_) if self.peek() == Token::LBracket => {
+                        let elem = match type_token {
+                            Token::Int => Type::Int,
+                            Token::String => Type::Str,
+                            Token::Identifier(name) if self.type_params.contains(&name) => Type::Param(name),
+                            Token::Identifier(name) if !type_args.is_empty() => Type::Generic(name, type_args),
+                            Token::Identifier(name) => Type::Named(name),
+                            _ => unreachable!(),
+                        };
+                        let elem = if nullable { Type::Nullable(Box::new(elem)) } else { elem };
+                        self.advance(); // consume '['
                         let size = self.parse_expr();
                         if self.advance() != Token::RBracket { panic!("Expected ']' after size"); }
-                        Expr::NewArray(Box::new(size))
+                        Expr::NewArray(elem, Box::new(size))
                     },
                     Token::Identifier(name) => {
+                        if self.type_params.contains(&name) { panic!("Cannot create an instance of type parameter '{}'", name); }
                         if self.advance() != Token::LParen { panic!("Expected '(' after class name"); }
+                        let mut args = Vec::new();
+                        if self.peek() != Token::RParen {
+                            loop {
+                                args.push(self.parse_arg());
+                                if self.peek() == Token::Comma { self.advance(); } else { break; }
+                            }
+                        }
                         if self.advance() != Token::RParen { panic!("Expected ')' after arguments"); }
-                        Expr::NewInstance(name)
+                        Expr::NewInstance(name, type_args, args)
                     },
                     _ => panic!("Expected type or class name after 'new'"),
                 }
             }
             Token::StringLit(s) => Expr::StringLiteral(s),
+            Token::Null => Expr::Null,
+            Token::Match => self.parse_match(),
+            Token::LParen if self.is_lambda() => self.parse_lambda(),
+            Token::LParen => {
+                let expr = self.parse_expr();
+                if self.peek() != Token::Comma {
+                    if self.advance() != Token::RParen { panic!("Expected ')' after expression"); }
+                    return expr;
+                }
+                // (a, b, ...) is a tuple
+                let mut elems = vec![expr];
+                while self.peek() == Token::Comma {
+                    self.advance(); // consume ','
+                    elems.push(self.parse_expr());
+                }
+                if self.advance() != Token::RParen { panic!("Expected ')' after tuple elements"); }
+                Expr::Tuple(elems)
+            }
+            Token::Super => {
+                // super(args) OR super.method(args)
+                let method = if self.peek() == Token::Dot {
+                    self.advance(); // consume '.'
+                    match self.advance() { Token::Identifier(m) => Some(m), _ => panic!("Expected method name after 'super.'") }
+                } else {
+                    None
+                };
+                if self.advance() != Token::LParen { panic!("Expected '(' after 'super'"); }
+                let mut args = Vec::new();
+                if self.peek() != Token::RParen {
+                    loop {
+                        args.push(self.parse_arg());
+                        if self.peek() == Token::Comma { self.advance(); } else { break; }
+                    }
+                }
+                if self.advance() != Token::RParen { panic!("Expected ')' after arguments"); }
+                Expr::SuperCall(method, args)
+            }
             Token::Identifier(name) => {
                 if self.peek() == Token::LParen {
                     self.advance(); // skip '('
                     let mut args = Vec::new();
                     if self.peek() != Token::RParen {
                         loop {
-                            args.push(self.parse_expr());
+                            args.push(self.parse_arg());
                             if self.peek() == Token::Comma { self.advance(); } else { break; }
                         }
                     }
@@ -153,24 +454,6 @@ impl Parser {
                     let index = self.parse_expr();
                     if self.advance() != Token::RBracket { panic!("Expected ']'"); }
                     Expr::ArrayAccess(name, Box::new(index))
-                } else if self.peek() == Token::Dot {
-                    self.advance(); // consume '.'
-                    let member = match self.advance() { Token::Identifier(f) => f, _ => panic!("Expected member name") };
-                    
-                    if self.peek() == Token::LParen {
-                        self.advance(); // consume '('
-                        let mut args = Vec::new();
-                        if self.peek() != Token::RParen {
-                            loop {
-                                args.push(self.parse_expr());
-                                if self.peek() == Token::Comma { self.advance(); } else { break; }
-                            }
-                        }
-                        if self.advance() != Token::RParen { panic!("Expected ')' after arguments"); }
-                        Expr::MethodCall(Box::new(Expr::Variable(name)), member, args)
-                    } else {
-                        Expr::GetField(Box::new(Expr::Variable(name)), member)
-                    }
                 } else {
                     Expr::Variable(name)
                 }
@@ -182,158 +465,698 @@ impl Parser {
         }
     }
 
-    fn parse_function(&mut self, symbols: &mut SymbolTable) -> Stmt {
-        self.advance(); // consume Return Type (int/void)
+    // Called after '(': a lambda's parameter list is followed by '->'
+    fn is_lambda(&self) -> bool {
+        let mut depth = 1;
+        let mut n = 0;
+        while depth > 0 {
+            match self.peek_n(n) {
+                Token::LParen => depth += 1,
+                Token::RParen => depth -= 1,
+                Token::Eof => return false,
+                _ => {}
+            }
+            n += 1;
+        }
+        self.peek_n(n) == Token::Arrow
+    }
+
+    // (int x, int y) -> x + y  |  (int x) -> { ... }
+    fn parse_lambda(&mut self) -> Expr {
+        let params = self.parse_params();
+        Self::reject_defaults(&params, "a lambda");
+        self.advance(); // consume '->'
+        let body = if self.peek() == Token::LBrace {
+            match self.parse_block_body("lambda") {
+                Stmt::Block(statements) => statements,
+                _ => unreachable!(),
+            }
+        } else {
+            vec![Stmt::Return(self.parse_expr())]
+        };
+        Expr::Lambda(params, body)
+    }
+
+    // match value { pattern => expr | { block } ... }, one arm per line (or comma separated)
+    fn parse_match(&mut self) -> Expr {
+        let scrutinee = self.parse_expr();
+        if self.advance() != Token::LBrace { panic!("Expected '{{' after match value"); }
+
+        let mut arms = Vec::new();
+        while !self.is_at_end() && self.peek() != Token::RBrace {
+            if matches!(self.peek(), Token::Newline | Token::Comma) { self.advance(); continue; }
+            let pattern = self.parse_pattern();
+            if self.advance() != Token::FatArrow { panic!("Expected '=>' after match pattern"); }
+            let body = if self.peek() == Token::LBrace {
+                self.parse_block_body("match arm")
+            } else {
+                Stmt::Expression(self.parse_expr())
+            };
+            arms.push(MatchArm { pattern, body });
+        }
+        if self.advance() != Token::RBrace { panic!("Expected '}}' after match arms"); }
+        Expr::Match(Box::new(scrutinee), arms)
+    }
+
+    fn parse_pattern(&mut self) -> Pattern {
+        match self.advance() {
+            Token::Number(n) => Pattern::Integer(n as i32),
+            Token::StringLit(s) => Pattern::StringLiteral(s),
+            Token::Identifier(name) if self.peek() == Token::Dot => {
+                let name = match self.is_module_type_at(2, &name) {
+                    true => self.parse_type_name(name), // alias.Enum.Variant
+                    false => self.type_name(name),
+                };
+                self.advance(); // consume '.'
+                let variant = match self.advance() { Token::Identifier(v) => v, _ => panic!("Expected variant name after '{}.'", name) };
+                let mut bindings = Vec::new();
+                if self.peek() == Token::LParen {
+                    self.advance(); // consume '('
+                    while self.peek() != Token::RParen {
+                        match self.advance() { Token::Identifier(b) => bindings.push(b), _ => panic!("Expected a name to bind in pattern '{}.{}'", name, variant) }
+                        if self.peek() == Token::Comma { self.advance(); } else { break; }
+                    }
+                    if self.advance() != Token::RParen { panic!("Expected ')' after pattern bindings"); }
+                }
+                Pattern::Variant(name, variant, bindings)
+            }
+            Token::Identifier(name) => Pattern::Binding(name),
+            tok => panic!("Unexpected token in match pattern: {:?}", tok),
+        }
+    }
+
+    fn parse_function(&mut self, symbols: &mut SymbolTable, visibility: Visibility) -> Stmt {
+        let mut return_type = self.parse_type();
 
         let name_token = self.advance();
         let name = match name_token {
-            Token::Identifier(n) => n,
+            Token::Identifier(n) => self.scope.qualify(&n),
             _ => panic!("Expected function name, found {:?}", name_token),
         };
 
+        // Generic function: T identity<T>(T x). The return type came before '<T>', so rebind it.
+        let type_params = if self.peek() == Token::LessThan { self.parse_type_params() } else { Vec::new() };
+        let outer_params = std::mem::replace(&mut self.type_params, type_params.clone());
+        return_type = bind_type_params(return_type, &type_params);
+
         // Parse Parameters
         if self.advance() != Token::LParen { panic!("Expected '(' after function name"); }
+        let params = self.parse_params();
+
+        // Register function in the symbol table (Pass 1: Discovery)
+        symbols.functions.insert(name.clone(), FunctionInfo {
+            name: name.clone(),
+            address: 0, // Placeholder: Will be resolved during emission
+            local_count: 0,
+            signature: Signature { params: params.iter().map(|p| p.ty.clone()).collect(), return_type: return_type.clone() },
+            visibility,
+            type_params,
+            param_names: params.iter().map(|p| p.name.clone()).collect(),
+            defaults: params.iter().map(|p| p.default.clone()).collect(),
+            variadic: params.last().is_some_and(|p| p.variadic),
+        });
+
+        // Parse Body (parameters and locals are scoped by the Emitter)
+        let body_stmt = self.parse_block(symbols);
+        let body = match body_stmt { Stmt::Block(stmts) => stmts, _ => vec![] };
+        self.type_params = outer_params;
+
+        Stmt::Function(FunctionDecl { name, params, return_type, body })
+    }
+
+    // Optional public/private/protected modifier (members default to public)
+    fn parse_visibility(&mut self) -> Visibility {
+        let visibility = match self.peek() {
+            Token::Public => Visibility::Public,
+            Token::Private => Visibility::Private,
+            Token::Protected => Visibility::Protected,
+            _ => return Visibility::Public,
+        };
+        self.advance();
+        visibility
+    }
+
+    // Parses a type: int, String, void, var or a class/interface name, with any number of '[]'
+    fn parse_type(&mut self) -> Type {
+        let mut ty = match self.advance() {
+            Token::Int => Type::Int,
+            Token::String => Type::Str,
+            Token::Void => Type::Void,
+            Token::Var => Type::Infer,
+            Token::LParen => {
+                let mut elems = Vec::new();
+                loop {
+                    let elem = self.parse_type();
+                    if matches!(elem, Type::Void | Type::Infer) { panic!("Tuple element cannot be '{}'", elem); }
+                    elems.push(elem);
+                    if self.peek() == Token::Comma { self.advance(); } else { break; }
+                }
+                if self.advance() != Token::RParen { panic!("Expected ')' after tuple element types"); }
+                if elems.len() < 2 { panic!("A tuple type needs at least two elements"); }
+                Type::Tuple(elems)
+            }
+            Token::Identifier(name) if self.type_params.contains(&name) => Type::Param(name),
+            Token::Identifier(name) => {
+                let name = self.parse_type_name(name);
+                if self.peek() == Token::LessThan { Type::Generic(name, self.parse_type_args()) } else { Type::Named(name) }
+            }
+            Token::Func => {
+                // func(int, String) -> int; without '->' the function returns void
+                if self.advance() != Token::LParen { panic!("Expected '(' after 'func'"); }
+                let mut params = Vec::new();
+                if self.peek() != Token::RParen {
+                    loop {
+                        params.push(self.parse_type());
+                        if self.peek() == Token::Comma { self.advance(); } else { break; }
+                    }
+                }
+                if self.advance() != Token::RParen { panic!("Expected ')' after parameter types"); }
+                let ret = if self.peek() == Token::Arrow {
+                    self.advance(); // consume '->'
+                    self.parse_type()
+                } else {
+                    Type::Void
+                };
+                Type::Function(params, Box::new(ret))
+            }
+            tok => panic!("Expected type, found {:?}", tok),
+        };
+        // Suffixes apply left to right: Point?[] is an array of nullable points, Point[]? a nullable array
+        loop {
+            if self.peek() == Token::LBracket && self.peek_n(1) == Token::RBracket {
+                self.advance(); // consume '['
+                self.advance(); // consume ']'
+                ty = Type::Array(Box::new(ty));
+            } else if self.peek() == Token::Question {
+                self.advance(); // consume '?'
+                if ty.is_nullable() { panic!("Type '{}' is already nullable", ty); }
+                ty = Type::Nullable(Box::new(ty));
+            } else {
+                break;
+            }
+        }
+        ty
+    }
+
+    // The class after 'is'/'as'. Unlike parse_type there is no '[]' suffix, and '?' only after 'as',
+    // so 'shape is Circle ? 1 : 0' stays a conditional
+    fn parse_class_target(&mut self, op: &str, nullable: bool) -> Type {
+        let ty = match self.advance() {
+            Token::Identifier(name) if self.type_params.contains(&name) => Type::Param(name),
+            Token::Identifier(name) => {
+                let name = self.parse_type_name(name);
+                if self.peek() == Token::LessThan { Type::Generic(name, self.parse_type_args()) } else { Type::Named(name) }
+            }
+            tok => panic!("Expected class name after '{}', found {:?}", op, tok),
+        };
+        if nullable && self.peek() == Token::Question {
+            self.advance(); // consume '?'
+            return Type::Nullable(Box::new(ty));
+        }
+        ty
+    }
+
+    // <int, String[]> after a generic class name
+    fn parse_type_args(&mut self) -> Vec<Type> {
+        self.advance(); // consume '<'
+        let mut args = Vec::new();
+        loop {
+            let ty = self.parse_type();
+            if matches!(ty, Type::Void | Type::Infer) { panic!("Type argument cannot be '{}'", ty); }
+            args.push(ty);
+            if self.peek() == Token::Comma { self.advance(); } else { break; }
+        }
+        if self.advance() != Token::GreaterThan { panic!("Expected '>' after type arguments"); }
+        args
+    }
+
+    // <T, U> after the name of a generic class or function
+    fn parse_type_params(&mut self) -> Vec<String> {
+        self.advance(); // consume '<'
+        let mut params: Vec<String> = Vec::new();
+        loop {
+            match self.advance() {
+                Token::Identifier(p) if params.contains(&p) => panic!("Duplicate type parameter '{}'", p),
+                Token::Identifier(p) => params.push(p),
+                tok => panic!("Expected type parameter name, found {:?}", tok),
+            }
+            if self.peek() == Token::Comma { self.advance(); } else { break; }
+        }
+        if self.advance() != Token::GreaterThan { panic!("Expected '>' after type parameters"); }
+        params
+    }
+
+    // Type -> Identifier -> '(' (or '<' for a generic function)
+    fn is_function_start(&self) -> bool {
+        let len = self.type_len_at(0);
+        len > 0 && matches!(self.peek_n(len), Token::Identifier(_)) && matches!(self.peek_n(len + 1), Token::LParen | Token::LessThan)
+    }
+
+    // Number of tokens the type starting n tokens ahead would span (0 if no type starts there)
+    fn type_len_at(&self, n: usize) -> usize {
+        if self.peek_n(n) == Token::Func {
+            return self.func_type_len_at(n);
+        }
+        let mut len = match self.peek_n(n) {
+            Token::LParen => self.tuple_type_len_at(n),
+            Token::Identifier(name) if self.is_module_type_at(n + 1, &name) => match self.peek_n(n + 3) {
+                Token::LessThan => 3 + self.type_args_len_at(n + 3),
+                _ => 3,
+            },
+            Token::Identifier(_) if self.peek_n(n + 1) == Token::LessThan => 1 + self.type_args_len_at(n + 1),
+            Token::Int | Token::Void | Token::String | Token::Var | Token::Identifier(_) => 1,
+            _ => 0,
+        };
+        if len == 0 { return 0; }
+        loop {
+            if self.peek_n(n + len) == Token::LBracket && self.peek_n(n + len + 1) == Token::RBracket {
+                len += 2;
+            } else if self.peek_n(n + len) == Token::Question {
+                len += 1;
+            } else {
+                break;
+            }
+        }
+        len
+    }
+
+    // <...> spans up to its matching '>' if only type tokens appear inside (0 otherwise, e.g. for 'a < b')
+    fn type_args_len_at(&self, n: usize) -> usize {
+        let mut depth = 0;
+        let mut len = 0;
+        loop {
+            match self.peek_n(n + len) {
+                Token::LessThan => depth += 1,
+                Token::GreaterThan => depth -= 1,
+                Token::Int | Token::String | Token::Void | Token::Func | Token::Identifier(_) | Token::Comma | Token::Dot
+                | Token::LBracket | Token::RBracket | Token::LParen | Token::RParen | Token::Arrow | Token::Question => {}
+                _ => return 0,
+            }
+            len += 1;
+            if depth == 0 { return len; }
+        }
+    }
+
+    // (int, String) spans up to its matching ')' if only type tokens appear inside and a comma separates
+    // elements (0 otherwise, e.g. for '(a + b)')
+    fn tuple_type_len_at(&self, n: usize) -> usize {
+        let mut depth = 0;
+        let mut len = 0;
+        let mut elements = false;
+        loop {
+            match self.peek_n(n + len) {
+                Token::LParen => depth += 1,
+                Token::RParen => depth -= 1,
+                Token::Comma if depth == 1 => elements = true,
+                Token::Int | Token::String | Token::Func | Token::Identifier(_) | Token::Comma | Token::LessThan
+                | Token::GreaterThan | Token::LBracket | Token::RBracket | Token::Arrow | Token::Question => {}
+                _ => return 0,
+            }
+            len += 1;
+            if depth == 0 { return if elements { len } else { 0 }; }
+        }
+    }
+
+    // func(...) spans up to its ')' and the return type after '->', if any
+    fn func_type_len_at(&self, n: usize) -> usize {
+        if self.peek_n(n + 1) != Token::LParen { return 0; }
+        let mut depth = 0;
+        let mut len = 1;
+        loop {
+            match self.peek_n(n + len) {
+                Token::LParen => depth += 1,
+                Token::RParen => depth -= 1,
+                Token::Eof => return 0,
+                _ => {}
+            }
+            len += 1;
+            if depth == 0 { break; }
+        }
+        if self.peek_n(n + len) == Token::Arrow {
+            let ret = self.type_len_at(n + len + 1);
+            if ret == 0 { return 0; }
+            len += 1 + ret;
+        }
+        len
+    }
+
+    // Parses `[mut] Type name, ...` up to and including the closing ')'
+    fn parse_params(&mut self) -> Vec<Param> {
         let mut params = Vec::new();
         if self.peek() != Token::RParen {
             loop {
-                // Parse Parameter Type (e.g., "int")
-                if !matches!(self.peek(), Token::Int | Token::Void | Token::String) { panic!("Expected parameter type"); }
-                self.advance(); 
+                let mutable = self.peek() == Token::Mut;
+                if mutable { self.advance(); }
+
+                // Parse Parameter Type (e.g., "int", "Shape")
+                let mut ty = self.parse_type();
+                if matches!(ty, Type::Void | Type::Infer) { panic!("Parameter type cannot be '{}'", ty); }
+                let variadic = self.peek() == Token::Ellipsis;
+                if variadic {
+                    self.advance(); // consume '...'
+                    ty = Type::Array(Box::new(ty));
+                }
 
-                match self.advance() {
-                    Token::Identifier(param) => params.push(param),
+                let name = match self.advance() {
+                    Token::Identifier(name) => name,
                     _ => panic!("Expected parameter name"),
+                };
+                let default = if self.peek() == Token::Equals {
+                    self.advance(); // consume '='
+                    Some(self.parse_expr())
+                } else {
+                    None
+                };
+                if let Some(last) = params.last().filter(|p: &&Param| p.variadic) {
+                    panic!("Variadic parameter '{}' must be the last parameter", last.name);
+                }
+                if variadic && default.is_some() {
+                    panic!("Variadic parameter '{}' cannot have a default value: it is empty when no arguments are left", name);
                 }
+                if default.is_none() && !variadic && params.iter().any(|p: &Param| p.default.is_some()) {
+                    panic!("Parameter '{}' needs a default value: it follows one that has a default", name);
+                }
+                params.push(Param { name, ty, mutable, default, variadic });
                 if self.peek() == Token::Comma { self.advance(); } else { break; }
             }
         }
         if self.advance() != Token::RParen { panic!("Expected ')' after parameters"); }
+        params
+    }
 
-        // Register function in the symbol table (Pass 1: Discovery)
-        symbols.functions.insert(name.clone(), crate::semant::FunctionInfo {
-            name: name.clone(),
-            address: 0, // Placeholder: Will be resolved during emission
-        });
-
-        // Setup scope for function body
-        let old_locals = symbols.locals.clone();
-        let old_local_index = symbols.next_local_index;
-        symbols.locals.clear();
-        symbols.next_local_index = 0;
-
-        // Register parameters as locals
-        for param in &params {
-            symbols.locals.insert(param.clone(), symbols.next_local_index);
-            symbols.next_local_index += 1;
+    // Only functions, methods and constructors can be called with arguments left out or packed
+    fn reject_defaults(params: &[Param], context: &str) {
+        if let Some(p) = params.iter().find(|p| p.default.is_some()) {
+            panic!("Parameter '{}' of {} cannot have a default value", p.name, context);
         }
+        if let Some(p) = params.iter().find(|p| p.variadic) {
+            panic!("Parameter '{}' of {} cannot be variadic", p.name, context);
+        }
+    }
 
-        // Parse Body
-        let body_stmt = self.parse_block(symbols);
-        let body = match body_stmt { Stmt::Block(stmts) => stmts, _ => vec![] };
-
-        // Restore scope
-        symbols.locals = old_locals;
-        symbols.next_local_index = old_local_index;
-
-        Stmt::Function(name, params, body)
+    // A call argument, which may name its parameter: level: 3
+    fn parse_arg(&mut self) -> Expr {
+        if let (Token::Identifier(name), Token::Colon) = (self.peek(), self.peek_n(1)) {
+            self.advance(); // consume name
+            self.advance(); // consume ':'
+            return Expr::NamedArg(name, Box::new(self.parse_expr()));
+        }
+        self.parse_expr()
     }
 
     fn parse_class_decl(&mut self, symbols: &mut SymbolTable) -> Stmt {
         self.advance(); // consume 'class'
         let name = match self.advance() {
-            Token::Identifier(n) => n,
+            Token::Identifier(n) => self.scope.qualify(&n),
             _ => panic!("Expected class name"),
         };
+        let type_params = if self.peek() == Token::LessThan { self.parse_type_params() } else { Vec::new() };
+
+        let parent = if self.peek() == Token::Extends {
+            self.advance(); // consume 'extends'
+            let parent = match self.advance() { Token::Identifier(p) => self.parse_type_name(p), _ => panic!("Expected parent class name after 'extends'") };
+            if self.peek() == Token::LessThan { panic!("Class '{}' cannot extend generic class '{}'", name, parent); }
+            Some(parent)
+        } else {
+            None
+        };
+
+        let mut interfaces = Vec::new();
+        if self.peek() == Token::Implements {
+            self.advance(); // consume 'implements'
+            loop {
+                match self.advance() { Token::Identifier(i) => { let i = self.parse_type_name(i); interfaces.push(i) } _ => panic!("Expected interface name after 'implements'") }
+                if self.peek() == Token::Comma { self.advance(); } else { break; }
+            }
+        }
 
         if self.advance() != Token::LBrace { panic!("Expected '{{' after class name"); }
+        // Static members see the parameters too; the Emitter rejects them there
+        let outer_params = std::mem::replace(&mut self.type_params, type_params.clone());
+        let (fields, methods, static_methods) = self.parse_class_body(symbols, &name);
+        self.type_params = outer_params;
+        Stmt::Class(ClassDecl { name, type_params, parent, interfaces, fields, methods, static_methods, is_record: false })
+    }
+
+    // record Name(Type field, ...) [implements ...] [{ methods }]: a class of public final fields
+    fn parse_record_decl(&mut self, symbols: &mut SymbolTable) -> Stmt {
+        self.advance(); // consume 'record'
+        let name = match self.advance() {
+            Token::Identifier(n) => self.scope.qualify(&n),
+            _ => panic!("Expected record name"),
+        };
+        if self.peek() == Token::LessThan { panic!("Record '{}' cannot be generic", name); }
+        if self.advance() != Token::LParen { panic!("Expected '(' after record name"); }
+        let mut fields: Vec<Field> = self.parse_params().into_iter().map(|p| {
+            if p.mutable { panic!("Field '{}' of record '{}' cannot be 'mut': record fields are final", p.name, name); }
+            if p.variadic { panic!("Field '{}' of record '{}' cannot be variadic", p.name, name); }
+            // A default becomes the default of the generated constructor's parameter
+            Field { name: p.name, ty: p.ty, is_final: true, is_static: false, visibility: Visibility::Public, initializer: p.default }
+        }).collect();
+
+        let mut interfaces = Vec::new();
+        if self.peek() == Token::Implements {
+            self.advance(); // consume 'implements'
+            loop {
+                match self.advance() { Token::Identifier(i) => { let i = self.parse_type_name(i); interfaces.push(i) } _ => panic!("Expected interface name after 'implements'") }
+                if self.peek() == Token::Comma { self.advance(); } else { break; }
+            }
+        }
 
+        let (mut methods, mut static_methods) = (Vec::new(), Vec::new());
+        if self.peek() == Token::LBrace {
+            self.advance(); // consume '{'
+            let (extra_fields, body_methods, body_static_methods) = self.parse_class_body(symbols, &name);
+            if let Some(f) = extra_fields.iter().find(|f| !f.is_static) {
+                panic!("Record '{}' cannot declare field '{}': its fields are the ones in its header", name, f.name);
+            }
+            if body_methods.iter().any(|m| m.name == format!("{}_{}", name, name)) {
+                panic!("Record '{}' cannot declare a constructor: it is generated from the fields", name);
+            }
+            fields.extend(extra_fields);
+            methods = body_methods;
+            static_methods = body_static_methods;
+        }
+        Stmt::Class(ClassDecl { name, type_params: Vec::new(), parent: None, interfaces, fields, methods, static_methods, is_record: true })
+    }
+
+    // Members up to and including the closing '}'
+    fn parse_class_body(&mut self, symbols: &mut SymbolTable, name: &str) -> (Vec<Field>, Vec<FunctionDecl>, Vec<FunctionDecl>) {
+        let name = name.to_string();
         let mut fields = Vec::new();
         let mut methods = Vec::new();
+        let mut static_methods = Vec::new();
 
         while !self.is_at_end() && self.peek() != Token::RBrace {
             if self.peek() == Token::Newline { self.advance(); continue; }
-            
-            // Lookahead: Type -> Name. If next is '(', it's a method. Else field.
-            if matches!(self.peek_n(1), Token::Identifier(_)) && self.peek_n(2) == Token::LParen {
-                methods.push(self.parse_method(symbols, &name));
+
+            // Modifiers: [public|private|protected] [static]
+            let visibility = self.parse_visibility();
+            let is_static = self.peek() == Token::Static;
+            if is_static { self.advance(); }
+
+            // Constructor: ClassName(params) { ... } has no return type (the name is written unqualified)
+            let short_name = name.rsplit('.').next().unwrap().to_string();
+            if self.peek() == Token::Identifier(short_name) && self.peek_n(1) == Token::LParen {
+                if is_static { panic!("Constructor of '{}' cannot be static", name); }
+                methods.push(self.parse_method(symbols, &name, true, false, visibility));
+            // Lookahead: Type -> Name. If next is '(', it's a method (as is Type -> 'operator'). Else field.
+            } else if self.type_len_at(0) > 0 && (self.peek_n(self.type_len_at(0)) == Token::Operator
+                || matches!(self.peek_n(self.type_len_at(0)), Token::Identifier(_)) && self.peek_n(self.type_len_at(0) + 1) == Token::LParen) {
+                if is_static {
+                    static_methods.push(self.parse_method(symbols, &name, false, true, visibility));
+                } else {
+                    methods.push(self.parse_method(symbols, &name, false, false, visibility));
+                }
             } else {
                 // Parse field
-                if !matches!(self.advance(), Token::Int | Token::String | Token::Identifier(_)) { panic!("Expected field type"); }
+                let is_final = self.peek() == Token::Final;
+                if is_final { self.advance(); }
+                let ty = self.parse_type();
+                if matches!(ty, Type::Void | Type::Infer) { panic!("Field type cannot be '{}'", ty); }
                 let field_name = match self.advance() { Token::Identifier(n) => n, _ => panic!("Expected field name") };
-                fields.push(field_name);
+                let initializer = if self.peek() == Token::Equals {
+                    self.advance(); // consume '='
+                    Some(self.parse_expr())
+                } else {
+                    None
+                };
+                fields.push(Field { name: field_name, ty, is_final, is_static, visibility, initializer });
             }
         }
         if self.advance() != Token::RBrace { panic!("Expected '}}' after class body"); }
-        Stmt::Class(name, fields, methods)
+        (fields, methods, static_methods)
     }
 
-    fn parse_method(&mut self, symbols: &mut SymbolTable, class_name: &str) -> Stmt {
-        self.advance(); // consume Return Type
-        
-        let name_token = self.advance();
-        let method_name = match name_token { Token::Identifier(n) => n, _ => panic!("Expected method name") };
+    // interface Name { Type method(params) ... } lists required methods without bodies
+    fn parse_interface_decl(&mut self) -> Stmt {
+        self.advance(); // consume 'interface'
+        let name = match self.advance() {
+            Token::Identifier(n) => self.scope.qualify(&n),
+            _ => panic!("Expected interface name"),
+        };
+        if self.advance() != Token::LBrace { panic!("Expected '{{' after interface name"); }
+
+        let mut methods = Vec::new();
+        while !self.is_at_end() && self.peek() != Token::RBrace {
+            if self.peek() == Token::Newline { self.advance(); continue; }
+            let return_type = self.parse_type();
+            let method_name = self.parse_method_name();
+            if self.advance() != Token::LParen { panic!("Expected '(' after method name"); }
+            let params = self.parse_params();
+            Self::reject_defaults(&params, &format!("interface method '{}.{}'", name, method_name));
+            Self::check_operator(&method_name, &params, &return_type, false);
+            methods.push(MethodSig { name: method_name, params, return_type });
+        }
+        if self.advance() != Token::RBrace { panic!("Expected '}}' after interface body"); }
+        Stmt::Interface(InterfaceDecl { name, methods })
+    }
+
+    // enum Name { A, B = 5, C } : values count up from 0, or from the last explicit value.
+    // Variants may carry data: enum Shape { Circle(int r), Rect(int w, int h) }
+    fn parse_enum_decl(&mut self) -> Stmt {
+        self.advance(); // consume 'enum'
+        let name = match self.advance() {
+            Token::Identifier(n) => self.scope.qualify(&n),
+            _ => panic!("Expected enum name"),
+        };
+        if self.advance() != Token::LBrace { panic!("Expected '{{' after enum name"); }
+
+        let mut variants = Vec::new();
+        while !self.is_at_end() && self.peek() != Token::RBrace {
+            if matches!(self.peek(), Token::Newline | Token::Comma) { self.advance(); continue; }
+            let variant = match self.advance() { Token::Identifier(v) => v, tok => panic!("Expected variant name in enum '{}', found {:?}", name, tok) };
+            let fields = if self.peek() == Token::LParen {
+                self.advance(); // consume '('
+                let fields = self.parse_params();
+                Self::reject_defaults(&fields, &format!("variant '{}.{}'", name, variant));
+                fields
+            } else {
+                Vec::new()
+            };
+            let value = if self.peek() == Token::Equals {
+                self.advance(); // consume '='
+                match self.advance() { Token::Number(n) => Some(n as i32), _ => panic!("Enum values must be non-negative integer literals") }
+            } else {
+                None
+            };
+            variants.push(VariantDecl { name: variant, value, fields });
+        }
+        if self.advance() != Token::RBrace { panic!("Expected '}}' after enum body"); }
+        Stmt::Enum(EnumDecl { name, variants })
+    }
+
+    fn parse_method(&mut self, symbols: &mut SymbolTable, class_name: &str, is_constructor: bool, is_static: bool, visibility: Visibility) -> FunctionDecl {
+        let return_type = if is_constructor { Type::Void } else { self.parse_type() };
         
-        // Mangle name: Class_Method
+        let method_name = self.parse_method_name();
+        let method_name = if is_constructor { class_name.to_string() } else { method_name };
+
+        // Mangle name: Class_Method (constructors become Class_Class)
         let full_name = format!("{}_{}", class_name, method_name);
 
         // Parse Parameters
         if self.advance() != Token::LParen { panic!("Expected '(' after method name"); }
-        let mut params = Vec::new();
         
-        // Implicit 'this' parameter is handled in the symbol table scope below, 
-        // but we don't add it to 'params' AST because the caller won't provide it explicitly.
-        // However, for the bytecode generation to work easily, we can treat 'this' as local variable 0.
+        // Implicit 'this' parameter is not parsed here because the caller won't provide it explicitly.
+        // It is prepended to 'params' below so the bytecode can treat 'this' as local variable 0.
+        let mut params = self.parse_params();
+        Self::check_operator(&method_name, &params, &return_type, is_static);
 
-        if self.peek() != Token::RParen {
-            loop {
-                if !matches!(self.peek(), Token::Int | Token::Void | Token::String) { panic!("Expected parameter type"); }
-                self.advance(); 
-                match self.advance() {
-                    Token::Identifier(param) => params.push(param),
-                    _ => panic!("Expected parameter name"),
-                }
-                if self.peek() == Token::Comma { self.advance(); } else { break; }
-            }
+        // Prepend 'this' to params for the AST so the Emitter registers it as local variable 0.
+        // Static methods have no receiver.
+        if !is_static {
+            // In a generic class, 'this' is Box<T>
+            let this_ty = if self.type_params.is_empty() {
+                Type::Named(class_name.to_string())
+            } else {
+                Type::Generic(class_name.to_string(), self.type_params.iter().map(|p| Type::Param(p.clone())).collect())
+            };
+            params.insert(0, Param { name: "this".to_string(), ty: this_ty, mutable: false, default: None, variadic: false });
         }
-        if self.advance() != Token::RParen { panic!("Expected ')' after parameters"); }
 
         // Register function
-        symbols.functions.insert(full_name.clone(), crate::semant::FunctionInfo {
+        symbols.functions.insert(full_name.clone(), FunctionInfo {
             name: full_name.clone(),
             address: 0,
+            local_count: 0,
+            signature: Signature { params: params.iter().map(|p| p.ty.clone()).collect(), return_type: return_type.clone() },
+            visibility,
+            type_params: Vec::new(),
+            param_names: params.iter().map(|p| p.name.clone()).collect(),
+            defaults: params.iter().map(|p| p.default.clone()).collect(),
+            variadic: params.last().is_some_and(|p| p.variadic),
         });
 
-        // Setup Scope
-        let old_locals = symbols.locals.clone();
-        let old_local_index = symbols.next_local_index;
-        symbols.locals.clear();
-        symbols.next_local_index = 0;
-
-        // 1. Inject 'this' as the first local variable (index 0)
-        symbols.locals.insert("this".to_string(), symbols.next_local_index);
-        symbols.next_local_index += 1;
-
-        // 2. Register other parameters
-        for param in &params {
-            symbols.locals.insert(param.clone(), symbols.next_local_index);
-            symbols.next_local_index += 1;
-        }
-
         let body_stmt = self.parse_block(symbols);
         let body = match body_stmt { Stmt::Block(stmts) => stmts, _ => vec![] };
 
-        symbols.locals = old_locals;
-        symbols.next_local_index = old_local_index;
+        FunctionDecl { name: full_name, params, return_type, body }
+    }
 
-        // Prepend 'this' to params for the AST so the Emitter knows it's a local variable
-        params.insert(0, "this".to_string());
+    // A method name, or 'operator' and the symbol it overloads: 'operator +' is named "operator+"
+    // How an operator that cannot be overloaded was written, for the error message
+    fn operator_text(tok: &Token) -> String {
+        let text = match tok {
+            Token::Unknown(c) => return c.to_string(),
+            Token::Identifier(name) => return name.clone(),
+            Token::GreaterThan => ">",
+            Token::BangEqual => "!=",
+            Token::Equals => "=",
+            Token::Question => "?",
+            Token::QuestionQuestion => "??",
+            Token::QuestionDot => "?.",
+            Token::Dot => ".",
+            Token::LBracket => "[",
+            Token::LParen => "(",
+            Token::Arrow => "->",
+            Token::FatArrow => "=>",
+            _ => return format!("{:?}", tok),
+        };
+        text.to_string()
+    }
 
-        Stmt::Function(full_name, params, body)
+    fn parse_method_name(&mut self) -> String {
+        match self.advance() {
+            Token::Identifier(n) => n,
+            Token::Operator => {
+                let symbol = match self.advance() {
+                    Token::Plus => "+",
+                    Token::Minus => "-",
+                    Token::Star => "*",
+                    Token::Slash => "/",
+                    Token::LessThan => "<",
+                    Token::EqualEqual => "==",
+                    Token::LBracket if self.peek() == Token::RBracket => {
+                        self.advance(); // consume ']'
+                        "[]"
+                    }
+                    tok => panic!("Operator '{}' cannot be overloaded", Self::operator_text(&tok)),
+                };
+                format!("operator{}", symbol)
+            }
+            _ => panic!("Expected method name"),
+        }
     }
 
-    fn parse_declaration(&mut self) -> Stmt {
-        self.advance(); // consume Type (int/var)
-        
+    // Operators take the right operand (or the index) as their only parameter; comparisons give an int
+    fn check_operator(name: &str, params: &[Param], return_type: &Type, is_static: bool) {
+        // Not 'operatorCount': identifiers cannot continue with a symbol
+        let Some(symbol) = name.strip_prefix("operator").filter(|s| s.starts_with(|c: char| !c.is_alphanumeric() && c != '_')) else { return };
+        if is_static { panic!("Operator '{}' cannot be static", symbol); }
+        if params.len() != 1 { panic!("Operator '{}' must take exactly one parameter", symbol); }
+        if matches!(symbol, "==" | "<") && *return_type != Type::Int {
+            panic!("Operator '{}' must return int", symbol);
+        }
+        if *return_type == Type::Void { panic!("Operator '{}' must return a value", symbol); }
+    }
+
+    fn parse_declaration(&mut self, is_final: bool) -> Stmt {
+        let ty = self.parse_type();
+        if ty == Type::Infer && self.peek() == Token::LParen {
+            return self.parse_destructure(is_final);
+        }
+
         let name = match self.advance() {
             Token::Identifier(n) => n,
             _ => panic!("Expected variable name"),
@@ -342,7 +1165,25 @@ impl Parser {
         if self.advance() != Token::Equals { panic!("Expected '=' after variable name"); }
         
         let initializer = self.parse_expr();
-        Stmt::VarDecl(name, initializer)
+        Stmt::VarDecl(name, ty, initializer, is_final)
+    }
+
+    // var (a, b) = tuple, after 'var'
+    fn parse_destructure(&mut self, is_final: bool) -> Stmt {
+        self.advance(); // consume '('
+        let mut names = Vec::new();
+        loop {
+            match self.advance() {
+                Token::Identifier(n) if n != "_" && names.contains(&n) => panic!("Duplicate variable '{}' in destructuring", n),
+                Token::Identifier(n) => names.push(n),
+                tok => panic!("Expected variable name in destructuring, found {:?}", tok),
+            }
+            if self.peek() == Token::Comma { self.advance(); } else { break; }
+        }
+        if self.advance() != Token::RParen { panic!("Expected ')' after destructured names"); }
+        if names.len() < 2 { panic!("Destructuring needs at least two names"); }
+        if self.advance() != Token::Equals { panic!("Expected '=' after destructured names"); }
+        Stmt::Destructure(names, self.parse_expr(), is_final)
     }
 
     fn parse_block(&mut self, symbols: &mut SymbolTable) -> Stmt {
@@ -363,6 +1204,15 @@ impl Parser {
         Stmt::Block(statements)
     }
 
+    // Block inside an expression (a match arm or a lambda body). Expressions are parsed without
+    // the symbol table, so nothing that registers functions may appear in it.
+    fn parse_block_body(&mut self, context: &str) -> Stmt {
+        let mut scratch = SymbolTable::new();
+        let block = self.parse_block(&mut scratch);
+        if !scratch.functions.is_empty() { panic!("Functions cannot be declared inside a {}", context); }
+        block
+    }
+
     fn parse_if(&mut self, symbols: &mut SymbolTable) -> Stmt {
         self.advance(); // skip 'if'
         let condition = self.parse_expr();
@@ -384,6 +1234,101 @@ impl Parser {
         Stmt::While(condition, body)
     }
 
+    // switch value { case 1, 2: ... default: ... } : cases don't fall through
+    fn parse_switch(&mut self, symbols: &mut SymbolTable) -> Stmt {
+        self.advance(); // skip 'switch'
+        let subject = self.parse_expr();
+        if self.advance() != Token::LBrace { panic!("Expected '{{' after switch value"); }
+
+        let mut cases = Vec::new();
+        let mut default = None;
+        loop {
+            while self.peek() == Token::Newline { self.advance(); }
+            match self.advance() {
+                Token::Case => {
+                    let mut values = vec![self.parse_expr()];
+                    while self.peek() == Token::Comma {
+                        self.advance(); // consume ','
+                        values.push(self.parse_expr());
+                    }
+                    if self.advance() != Token::Colon { panic!("Expected ':' after case values"); }
+                    cases.push((values, self.parse_case_body(symbols)));
+                }
+                Token::Default => {
+                    if default.is_some() { panic!("A switch can only have one 'default'"); }
+                    if self.advance() != Token::Colon { panic!("Expected ':' after 'default'"); }
+                    default = Some(self.parse_case_body(symbols));
+                }
+                Token::RBrace => break,
+                tok => panic!("Expected 'case', 'default' or '}}' in switch, found {:?}", tok),
+            }
+        }
+        Stmt::Switch(subject, cases, default)
+    }
+
+    // Statements up to the next case label or the end of the switch
+    fn parse_case_body(&mut self, symbols: &mut SymbolTable) -> Vec<Stmt> {
+        let mut body = Vec::new();
+        while !self.is_at_end() && !matches!(self.peek(), Token::Case | Token::Default | Token::RBrace) {
+            if self.peek() == Token::Newline { self.advance(); continue; }
+            body.push(self.parse_statement(symbols));
+        }
+        body
+    }
+
+    // try { ... } catch (NotFound e) { ... } catch (e) { ... } finally { ... }
+    fn parse_try(&mut self, symbols: &mut SymbolTable) -> Stmt {
+        self.advance(); // skip 'try'
+        let body = self.parse_braced_body(symbols, "try");
+
+        let mut catches = Vec::new();
+        while self.peek() == Token::Catch {
+            self.advance(); // skip 'catch'
+            if self.advance() != Token::LParen { panic!("Expected '(' after 'catch'"); }
+            let len = self.type_len_at(0);
+            let ty = if len > 0 && matches!(self.peek_n(len), Token::Identifier(_)) {
+                self.parse_type()
+            } else {
+                Type::Named("Exception".to_string())
+            };
+            let name = match self.advance() {
+                Token::Identifier(name) => name,
+                tok => panic!("Expected exception variable name in 'catch', found {:?}", tok),
+            };
+            if self.advance() != Token::RParen { panic!("Expected ')' after catch variable"); }
+            catches.push(CatchClause { ty, name, body: self.parse_braced_body(symbols, "catch") });
+        }
+
+        let finally = if self.peek() == Token::Finally {
+            self.advance(); // skip 'finally'
+            Some(self.parse_braced_body(symbols, "finally"))
+        } else {
+            None
+        };
+        if catches.is_empty() && finally.is_none() {
+            panic!("'try' needs at least one 'catch' or a 'finally'");
+        }
+        Stmt::Try(body, catches, finally)
+    }
+
+    fn parse_braced_body(&mut self, symbols: &mut SymbolTable, keyword: &str) -> Vec<Stmt> {
+        if self.peek() != Token::LBrace { panic!("Expected '{{' after '{}'", keyword); }
+        match self.parse_block(symbols) {
+            Stmt::Block(statements) => statements,
+            _ => unreachable!(),
+        }
+    }
+
+    // A label is only taken from the same line: `break outer`
+    fn parse_jump_label(&mut self) -> Option<String> {
+        if let Token::Identifier(label) = self.peek() {
+            self.advance();
+            Some(label)
+        } else {
+            None
+        }
+    }
+
     fn parse_return(&mut self) -> Stmt {
         self.advance(); // skip 'return'
         let value = self.parse_expr();
@@ -403,8 +1348,21 @@ impl Parser {
         tok
     }
     fn peek_n(&self, n: usize) -> Token {
-        if self.pos + n >= self.tokens.len() { return Token::EOF; }
+        if self.pos + n >= self.tokens.len() { return Token::Eof; }
         self.tokens[self.pos + n].clone()
     }
-    fn is_at_end(&self) -> bool { self.peek() == Token::EOF }
+    fn is_at_end(&self) -> bool { self.peek() == Token::Eof }
+}
+
+// Turns the names of freshly declared type parameters into Type::Param
+fn bind_type_params(ty: Type, params: &[String]) -> Type {
+    match ty {
+        Type::Named(name) if params.contains(&name) => Type::Param(name),
+        Type::Array(elem) => Type::Array(Box::new(bind_type_params(*elem, params))),
+        Type::Generic(name, args) => Type::Generic(name, args.into_iter().map(|a| bind_type_params(a, params)).collect()),
+        Type::Function(ps, ret) => {
+            Type::Function(ps.into_iter().map(|p| bind_type_params(p, params)).collect(), Box::new(bind_type_params(*ret, params)))
+        }
+        ty => ty,
+    }
 }
@@ -0,0 +1,14 @@
+// amber-core/src/prelude.rs
+
+// Built-in declarations, compiled ahead of every program.
+// The VM raises its own runtime errors (division by zero, bad array index, ...) as Exception instances,
+// so 'message' must stay the first field.
+pub const SOURCE: &str = "
+class Exception {
+    final String message
+
+    Exception(String message) {
+        this.message = message
+    }
+}
+";
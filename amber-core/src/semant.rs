@@ -1,26 +1,188 @@
 // amber-core/src/semant.rs
 use std::collections::HashMap;
+use std::fmt;
+use crate::ast::{Expr, Param, Pattern, Stmt, Type, Visibility};
 
+#[derive(Clone, PartialEq)]
+pub struct Signature {
+    pub params: Vec<Type>,
+    pub return_type: Type,
+}
+
+// Printed as "int(String, int)" in error messages
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let params: Vec<String> = self.params.iter().map(|p| p.to_string()).collect();
+        write!(f, "{}({})", self.return_type, params.join(", "))
+    }
+}
+
+#[derive(Clone)]
 pub struct FunctionInfo {
     #[allow(dead_code)]
     pub name: String,
     pub address: u32, // Where it exists in the bytecode
+    pub local_count: u32, // Frame size: parameters plus locals declared in the body
+    pub signature: Signature, // For methods, the first parameter is 'this'
+    pub visibility: Visibility,
+    pub type_params: Vec<String>, // T identity<T>(T x); inferred from the arguments of each call
+    pub param_names: Vec<String>, // In signature order, for named arguments
+    pub defaults: Vec<Option<Expr>>, // Default value of each parameter, if it has one
+    pub variadic: bool, // The last parameter takes the remaining arguments as an array
+}
+
+#[derive(Clone)]
+pub struct FieldInfo {
+    pub index: u32,
+    pub ty: Type,
+    pub is_final: bool, // Only assignable through 'this' inside the constructor
+    pub owner: String,  // Declaring class (a parent, for inherited fields)
+    pub visibility: Visibility,
+}
+
+#[derive(Clone)]
+pub struct StaticField {
+    pub var: Variable, // Global slot
+    pub owner: String,
+    pub visibility: Visibility,
 }
 
 #[derive(Clone)]
 pub struct ClassInfo {
     pub name: String,
-    pub fields: HashMap<String, u32>, // Field Name -> Index
-    pub methods: Vec<String>, // List of method names
+    pub type_params: Vec<String>, // Bound by the type arguments of Box<int>
+    pub parent: Option<String>,
+    pub interfaces: Vec<String>, // Interfaces named in 'implements' (not those of the parents)
+    pub fields: HashMap<String, FieldInfo>, // Field Name -> Layout, inherited fields included
+    pub methods: Vec<String>, // List of method names declared by this class itself
+    pub static_methods: Vec<String>, // Called as Class.method(...), without a receiver
+    pub static_fields: HashMap<String, StaticField>, // Class-scoped globals declared by this class
+    pub constructor: Option<usize>, // Parameter count of ClassName(...), if declared
+    pub is_record: bool,            // Records are final: their generated equals only knows their own fields
+}
+
+#[derive(Clone)]
+pub struct EnumInfo {
+    pub name: String,
+    pub variants: Vec<VariantInfo>, // In declaration order
+}
+
+#[derive(Clone)]
+pub struct VariantInfo {
+    pub name: String,
+    pub value: i32,        // Tag
+    pub fields: Vec<Type>, // Payload types (empty for plain variants)
+}
+
+impl EnumInfo {
+    // Enums whose variants carry data are heap objects: field 0 holds the tag, the payload follows.
+    // Plain enums are just their integer value.
+    pub fn has_payloads(&self) -> bool {
+        self.variants.iter().any(|v| !v.fields.is_empty())
+    }
+
+    pub fn variant(&self, name: &str) -> &VariantInfo {
+        self.variants.iter().find(|v| v.name == name)
+            .unwrap_or_else(|| panic!("Enum '{}' has no variant '{}'", self.name, name))
+    }
+}
+
+pub struct InterfaceInfo {
+    #[allow(dead_code)]
+    pub name: String,
+    pub methods: Vec<(String, Signature)>, // Required methods, without 'this'
+}
+
+// Top-level names a module offers to the files importing it
+pub struct ModuleInfo {
+    pub exports: HashMap<String, String>, // Name -> qualified name (geometry.Point)
+    pub private: Vec<String>,             // File-private functions, only usable inside the module
+}
+
+// How the file being compiled sees top-level names. An imported module's own declarations are
+// qualified with its name so that two modules can each declare a 'Point'; the main file's are not.
+#[derive(Default)]
+pub struct ModuleScope {
+    pub prefix: Option<String>,                  // geometry; None for the main file
+    pub names: HashMap<String, String>,          // Usable name -> qualified name: own declarations and imports
+    pub ambiguous: HashMap<String, Vec<String>>, // Names imported from more than one module -> those modules
+    pub modules: HashMap<String, String>,        // Imported module's last segment -> its full name, for geometry.area()
+}
+
+impl ModuleScope {
+    // Name under which a top-level declaration of this module is registered
+    pub fn qualify(&self, name: &str) -> String {
+        match &self.prefix {
+            Some(prefix) => format!("{}.{}", prefix, name),
+            None => name.to_string(),
+        }
+    }
+
+    // Qualified name that an unqualified name stands for, if it is declared or imported here
+    pub fn resolve(&self, name: &str) -> Option<&str> {
+        if let Some(modules) = self.ambiguous.get(name) {
+            panic!("'{}' is ambiguous: it is imported from both '{}' and '{}'", name, modules[0], modules[1]);
+        }
+        self.names.get(name).map(String::as_str)
+    }
+
+    // Makes a module's name usable here; a declaration of the file itself wins over imports
+    pub fn import(&mut self, name: &str, qualified: &str, module: &str) {
+        match self.names.get(name) {
+            Some(existing) if existing != qualified => {
+                let other = existing.rsplit_once('.').map_or("", |(m, _)| m).to_string();
+                self.ambiguous.entry(name.to_string()).or_insert_with(|| vec![other]).push(module.to_string());
+            }
+            _ => { self.names.insert(name.to_string(), qualified.to_string()); }
+        }
+    }
+
+    pub fn declare(&mut self, name: &str) {
+        self.ambiguous.remove(name);
+        self.names.insert(name.to_string(), self.qualify(name));
+    }
+}
+
+// Where a resolved variable lives at runtime
+#[derive(Clone)]
+pub enum VarSlot {
+    Global(u32),  // Global Index
+    Local(u32),   // Local Index relative to FP
+    Const(Expr),  // Compile-time literal, inlined at every use
+}
+
+#[derive(Clone)]
+pub struct Variable {
+    pub slot: VarSlot,
+    pub ty: Type,
+    pub mutable: bool,
+}
+
+impl Variable {
+    // Consts take no runtime storage
+    fn uses_slot(&self) -> bool {
+        !matches!(self.slot, VarSlot::Const(_))
+    }
+}
+
+// Saved by enter_function and handed back to exit_function
+pub struct FrameState {
+    locals: Vec<HashMap<String, Variable>>,
+    next_local_index: u32,
+    max_local_index: u32,
 }
 
 pub struct SymbolTable {
     pub functions: HashMap<String, FunctionInfo>,
     pub classes: HashMap<String, ClassInfo>,
-    pub variables: HashMap<String, u32>, // Maps "x" -> 0 (Global Index)
-    pub locals: HashMap<String, u32>,    // Maps "n" -> 0 (Local Index relative to FP)
+    pub interfaces: HashMap<String, InterfaceInfo>,
+    pub enums: HashMap<String, EnumInfo>,
+    pub modules: HashMap<String, ModuleInfo>, // Imported modules by full name, once compiled
+    pub variables: Vec<HashMap<String, Variable>>, // Global scopes: file scope first, then nested top-level blocks
+    pub locals: Vec<HashMap<String, Variable>>,    // Block scopes of the current function (empty at top level)
     pub next_var_index: u32,
     pub next_local_index: u32,
+    pub max_local_index: u32, // High-water mark of local slots, i.e. the frame size
 }
 
 impl SymbolTable {
@@ -28,10 +190,371 @@ impl SymbolTable {
         Self { 
             functions: HashMap::new(),
             classes: HashMap::new(),
-            variables: HashMap::new(),
-            locals: HashMap::new(),
+            interfaces: HashMap::new(),
+            enums: HashMap::new(),
+            modules: HashMap::new(),
+            variables: vec![HashMap::new()],
+            locals: Vec::new(),
             next_var_index: 0,
             next_local_index: 0,
+            max_local_index: 0,
+        }
+    }
+
+    // Classes, interfaces and enums share one namespace of type names
+    pub fn type_exists(&self, name: &str) -> bool {
+        self.classes.contains_key(name) || self.interfaces.contains_key(name) || self.enums.contains_key(name)
+    }
+
+    // Iterates a class and its parents, nearest first
+    pub fn ancestors<'a>(&'a self, class_name: &str) -> impl Iterator<Item = &'a ClassInfo> + 'a {
+        let mut current = self.classes.get(class_name);
+        std::iter::from_fn(move || {
+            let cls = current?;
+            current = cls.parent.as_ref().and_then(|p| self.classes.get(p));
+            Some(cls)
+        })
+    }
+
+    // Walks up the hierarchy from 'class_name' to the first class that declares 'method'
+    pub fn find_method_owner(&self, class_name: &str, method: &str) -> Option<&ClassInfo> {
+        self.ancestors(class_name).find(|cls| cls.methods.iter().any(|m| m == method))
+    }
+
+    // Static members are inherited: Child.member finds the nearest declaring class
+    pub fn find_static_method_owner(&self, class_name: &str, method: &str) -> Option<&ClassInfo> {
+        self.ancestors(class_name).find(|cls| cls.static_methods.iter().any(|m| m == method))
+    }
+
+    pub fn find_static_field(&self, class_name: &str, field: &str) -> Option<&StaticField> {
+        self.ancestors(class_name).find_map(|cls| cls.static_fields.get(field))
+    }
+
+    // Type parameters of a generic class paired with the arguments of Box<int>
+    pub fn type_bindings(&self, ty: &Type) -> Vec<(String, Type)> {
+        match ty {
+            Type::Generic(name, args) => self.classes[name].type_params.iter().cloned().zip(args.iter().cloned()).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    // Signature of a method as seen by callers (without 'this') on a value of the given static type
+    pub fn method_signature(&self, receiver: &Type, method: &str) -> Option<Signature> {
+        let name = receiver.class_name()?;
+        if let Some(iface) = self.interfaces.get(name) {
+            return iface.methods.iter().find(|(m, _)| m == method).map(|(_, sig)| sig.clone());
+        }
+        let owner = self.find_method_owner(name, method)?;
+        let sig = &self.functions[&format!("{}_{}", owner.name, method)].signature;
+        let bindings = self.type_bindings(receiver);
+        Some(Signature {
+            params: sig.params[1..].iter().map(|p| p.substitute(&bindings)).collect(),
+            return_type: sig.return_type.substitute(&bindings),
+        })
+    }
+
+    // A value of type 'from' can be stored where 'to' is expected: same type, a subclass,
+    // or a class implementing the interface. Type arguments must match exactly.
+    // Nullable types take null and any value of their base type.
+    pub fn is_assignable(&self, from: &Type, to: &Type) -> bool {
+        match (from.class_name(), to) {
+            _ if from == to => true,
+            (_, Type::Nullable(base)) => *from == Type::Null || self.is_assignable(&from.non_null(), base),
+            (Some(sub), Type::Named(sup)) => {
+                self.ancestors(sub).any(|cls| cls.name == *sup || cls.interfaces.contains(sup))
+            }
+            // Tuples are immutable, so each element may be a subtype
+            (_, Type::Tuple(to_elems)) => matches!(from, Type::Tuple(from_elems)
+                if from_elems.len() == to_elems.len() && from_elems.iter().zip(to_elems).all(|(f, t)| self.is_assignable(f, t))),
+            _ => false,
+        }
+    }
+
+    pub fn in_function(&self) -> bool {
+        !self.locals.is_empty()
+    }
+
+    // Outside of any function or block: where a module's globals are declared
+    pub fn at_file_scope(&self) -> bool {
+        !self.in_function() && self.variables.len() == 1
+    }
+
+    // Resolves a name through the innermost scopes first, so inner declarations shadow outer ones.
+    // Function bodies see their own block scopes and then the globals.
+    pub fn lookup(&self, name: &str) -> Option<&Variable> {
+        self.locals.iter().rev().find_map(|scope| scope.get(name))
+            .or_else(|| self.variables.iter().rev().find_map(|scope| scope.get(name)))
+    }
+
+    // Whether a name resolves to a variable of the file scope itself: blocks reuse their global slots
+    // once they end, file-scope variables keep theirs
+    pub fn is_file_variable(&self, name: &str) -> bool {
+        !self.locals.iter().any(|scope| scope.contains_key(name))
+            && self.variables.iter().rposition(|scope| scope.contains_key(name)) == Some(0)
+    }
+
+    // Declares a variable in the innermost scope. Shadowing an enclosing scope is allowed,
+    // redeclaring a name in the same scope is not.
+    pub fn declare(&mut self, name: &str, ty: Type, mutable: bool) -> VarSlot {
+        let slot = if self.in_function() {
+            let idx = self.next_local_index;
+            self.next_local_index += 1;
+            self.max_local_index = self.max_local_index.max(self.next_local_index);
+            VarSlot::Local(idx)
+        } else {
+            let idx = self.next_var_index;
+            self.next_var_index += 1;
+            VarSlot::Global(idx)
+        };
+        self.insert(name, Variable { slot: slot.clone(), ty, mutable });
+        slot
+    }
+
+    // A global slot outside of any scope, never reused (static fields)
+    pub fn allocate_global(&mut self) -> VarSlot {
+        let idx = self.next_var_index;
+        self.next_var_index += 1;
+        VarSlot::Global(idx)
+    }
+
+    pub fn declare_const(&mut self, name: &str, ty: Type, value: Expr) {
+        self.insert(name, Variable { slot: VarSlot::Const(value), ty, mutable: false });
+    }
+
+    fn insert(&mut self, name: &str, var: Variable) {
+        let scope = if self.in_function() {
+            self.locals.last_mut().unwrap()
+        } else {
+            self.variables.last_mut().unwrap()
+        };
+        if scope.contains_key(name) {
+            panic!("Variable '{}' is already declared in this scope", name);
+        }
+        scope.insert(name.to_string(), var);
+    }
+
+    pub fn push_scope(&mut self) {
+        if self.in_function() {
+            self.locals.push(HashMap::new());
+        } else {
+            self.variables.push(HashMap::new());
+        }
+    }
+
+    // Leaving a block frees its slots for reuse by later declarations
+    pub fn pop_scope(&mut self) {
+        if self.in_function() {
+            let scope = self.locals.pop().unwrap();
+            self.next_local_index -= scope.values().filter(|v| v.uses_slot()).count() as u32;
+        } else {
+            let scope = self.variables.pop().unwrap();
+            self.next_var_index -= scope.values().filter(|v| v.uses_slot()).count() as u32;
+        }
+    }
+
+    // Opens a fresh frame whose outermost scope holds the parameters (slots 0..n)
+    pub fn enter_function(&mut self, params: &[Param]) -> FrameState {
+        let saved = FrameState {
+            locals: std::mem::replace(&mut self.locals, vec![HashMap::new()]),
+            next_local_index: std::mem::replace(&mut self.next_local_index, 0),
+            max_local_index: std::mem::replace(&mut self.max_local_index, 0),
+        };
+        for param in params {
+            self.declare(&param.name, param.ty.clone(), param.mutable);
+        }
+        saved
+    }
+
+    // Closes the current frame and returns its size in slots
+    pub fn exit_function(&mut self, saved: FrameState) -> u32 {
+        let frame_size = self.max_local_index;
+        self.locals = saved.locals;
+        self.next_local_index = saved.next_local_index;
+        self.max_local_index = saved.max_local_index;
+        frame_size
+    }
+}
+
+// Names a lambda body reads from its surroundings: used before (or without) being declared in it.
+// The emitter captures those that resolve to locals of the enclosing function.
+pub fn free_variables(params: &[Param], body: &[Stmt]) -> Vec<String> {
+    let mut walker = FreeVariables { scopes: vec![params.iter().map(|p| p.name.clone()).collect()], free: Vec::new(), assigned: Vec::new(), calls: false };
+    walker.stmts(body);
+    walker.free
+}
+
+// Names of enclosing variables the statements assign to. Invalidates null checks across loops and branches.
+pub fn assigned_variables(body: &[Stmt]) -> Vec<String> {
+    let mut walker = FreeVariables { scopes: vec![Vec::new()], free: Vec::new(), assigned: Vec::new(), calls: false };
+    walker.stmts(body);
+    walker.assigned
+}
+
+// Whether the statements call a function, method or constructor, which may assign globals and static fields
+pub fn contains_calls(body: &[Stmt]) -> bool {
+    let mut walker = FreeVariables { scopes: vec![Vec::new()], free: Vec::new(), assigned: Vec::new(), calls: false };
+    walker.stmts(body);
+    walker.calls
+}
+
+struct FreeVariables {
+    scopes: Vec<Vec<String>>,
+    free: Vec<String>,
+    assigned: Vec<String>, // Free names that are assigned
+    calls: bool,           // Whether any call is made
+}
+
+impl FreeVariables {
+    fn declare(&mut self, name: &str) {
+        self.scopes.last_mut().unwrap().push(name.to_string());
+    }
+
+    fn use_name(&mut self, name: &str) {
+        let declared = self.scopes.iter().any(|scope| scope.iter().any(|n| n == name));
+        if !declared && !self.free.iter().any(|n| n == name) {
+            self.free.push(name.to_string());
+        }
+    }
+
+    fn scoped(&mut self, stmts: &[Stmt]) {
+        self.scopes.push(Vec::new());
+        self.stmts(stmts);
+        self.scopes.pop();
+    }
+
+    fn stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::VarDecl(name, _, init, _) | Stmt::ConstDecl(name, _, init) => {
+                self.expr(init);
+                self.declare(name);
+            }
+            Stmt::Destructure(names, init, _) => {
+                self.expr(init);
+                names.iter().for_each(|n| self.declare(n));
+            }
+            Stmt::Assign(name, value) => {
+                self.use_name(name);
+                if self.free.contains(name) && !self.assigned.contains(name) {
+                    self.assigned.push(name.clone());
+                }
+                self.expr(value);
+            }
+            Stmt::ArraySet(name, index, value) => {
+                self.use_name(name);
+                self.expr(index);
+                self.expr(value);
+            }
+            Stmt::Return(expr) | Stmt::Print(expr) | Stmt::Expression(expr) | Stmt::Throw(Some(expr)) => self.expr(expr),
+            Stmt::Block(stmts) => self.scoped(stmts),
+            Stmt::If(cond, then_branch, else_branch) => {
+                self.expr(cond);
+                self.scoped(std::slice::from_ref(then_branch));
+                if let Some(else_branch) = else_branch {
+                    self.scoped(std::slice::from_ref(else_branch));
+                }
+            }
+            Stmt::While(cond, body) => {
+                self.expr(cond);
+                self.scoped(std::slice::from_ref(body));
+            }
+            Stmt::Switch(subject, cases, default) => {
+                self.expr(subject);
+                for (values, body) in cases {
+                    values.iter().for_each(|v| self.expr(v));
+                    self.scoped(body);
+                }
+                if let Some(body) = default {
+                    self.scoped(body);
+                }
+            }
+            Stmt::Try(body, catches, finally) => {
+                self.scoped(body);
+                for clause in catches {
+                    self.scopes.push(vec![clause.name.clone()]);
+                    self.stmts(&clause.body);
+                    self.scopes.pop();
+                }
+                if let Some(body) = finally {
+                    self.scoped(body);
+                }
+            }
+            Stmt::Labeled(_, body) => self.stmt(body),
+            Stmt::FieldSet(obj, _, value) => {
+                self.expr(obj);
+                self.expr(value);
+            }
+            // Declarations can't see the enclosing frame
+            Stmt::Function(_) | Stmt::Class(_) | Stmt::Interface(_) | Stmt::Enum(_) => {}
+            Stmt::Break(_) | Stmt::Continue(_) | Stmt::Throw(None) => {}
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Integer(_) | Expr::StringLiteral(_) | Expr::Null => {}
+            Expr::Variable(name) => self.use_name(name),
+            Expr::NewArray(_, size) => self.expr(size),
+            Expr::ArrayAccess(name, index) => {
+                self.use_name(name);
+                self.expr(index);
+            }
+            Expr::Call(name, args) => {
+                self.calls = true;
+                self.use_name(name); // May be a variable holding a function
+                args.iter().for_each(|a| self.expr(a));
+            }
+            Expr::MethodCall(obj, _, args) | Expr::SafeMethodCall(obj, _, args) | Expr::CallValue(obj, args) => {
+                self.calls = true;
+                self.expr(obj);
+                args.iter().for_each(|a| self.expr(a));
+            }
+            Expr::NewInstance(_, _, args) => {
+                self.calls = true;
+                args.iter().for_each(|a| self.expr(a));
+            }
+            Expr::GetField(obj, _) | Expr::SafeGetField(obj, _) | Expr::InstanceOf(obj, _) | Expr::Cast(obj, _)
+            | Expr::TupleElement(obj, _) | Expr::NamedArg(_, obj) => self.expr(obj),
+            Expr::Tuple(elems) | Expr::ValueHash(elems) | Expr::VarArgs(elems) => elems.iter().for_each(|e| self.expr(e)),
+            Expr::ValueText(value) => self.expr(value),
+            Expr::SuperCall(_, args) => {
+                self.calls = true;
+                self.use_name("this");
+                args.iter().for_each(|a| self.expr(a));
+            }
+            Expr::Conditional(cond, then_value, else_value) => {
+                self.expr(cond);
+                self.expr(then_value);
+                self.expr(else_value);
+            }
+            Expr::Binary(left, _, right) | Expr::Coalesce(left, right) | Expr::ValueEquals(left, right) => {
+                self.expr(left);
+                self.expr(right);
+            }
+            Expr::Match(scrutinee, arms) => {
+                self.expr(scrutinee);
+                for arm in arms {
+                    let bindings = match &arm.pattern {
+                        Pattern::Variant(_, _, names) => names.clone(),
+                        Pattern::Binding(name) => vec![name.clone()],
+                        Pattern::Integer(_) | Pattern::StringLiteral(_) => Vec::new(),
+                    };
+                    self.scopes.push(bindings);
+                    self.stmt(&arm.body);
+                    self.scopes.pop();
+                }
+            }
+            Expr::Lambda(params, body) => {
+                // What a nested lambda captures, this one must capture too
+                for name in free_variables(params, body) {
+                    self.use_name(&name);
+                }
+            }
         }
     }
 }
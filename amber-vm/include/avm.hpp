@@ -4,12 +4,36 @@
 #include <vector>
 #include <cstdint>
 #include <string>
+#include <unordered_map>
+
+// Runtime view of a class, keyed by its Class ID (the constant pool index of its name)
+struct ClassEntry {
+    int32_t parent_id = -1;                          // -1 for root classes
+    std::unordered_map<int32_t, uint32_t> methods;   // Method name constant -> Address (inherited included)
+};
+
+using ClassTable = std::unordered_map<int32_t, ClassEntry>;
+
+// One row of a function's exception table
+struct Handler {
+    uint32_t start, end; // Protected range [start, end)
+    uint32_t target;     // Handler address
+    int32_t class_id;    // Caught class (subclasses included), -1 catches anything
+};
+
+struct ExceptionTable {
+    uint32_t start, end;           // Bytecode range of the function (all code, for the top level)
+    uint32_t frame_size;           // The stack is cut back to FP + frame_size before a handler runs
+    std::vector<Handler> handlers; // Inner tries first
+};
+
+using ExceptionTables = std::vector<ExceptionTable>;
 
 // Forward-declare the loader function used in main.cpp
 namespace Loader {
-    bool load(const char* filename, std::vector<uint8_t>& bytecode, std::vector<std::string>& constants);
+    bool load(const char* filename, std::vector<uint8_t>& bytecode, std::vector<std::string>& constants, ClassTable& classes, ExceptionTables& tables);
 }
 
-void execute(const std::vector<uint8_t>& bytecode, std::vector<std::string>& constants);
+void execute(const std::vector<uint8_t>& bytecode, std::vector<std::string>& constants, const ClassTable& classes, const ExceptionTables& tables);
 
 #endif // AVM_HPP
\ No newline at end of file
@@ -7,6 +7,7 @@ enum OpCode : uint8_t {
     OP_HALT           = 0x00, // Stop execution
     OP_JUMP           = 0x01, // Unconditional jump by a 4-byte signed offset
     OP_JUMP_IF_FALSE  = 0x02, // Pop a value; jump if it's 0
+    OP_TABLE_SWITCH   = 0x03, // Operands: low (i32), count (u32), default offset (i32), count offsets (i32). Pop a value; jump by offsets[value - low] if in range, else by the default. Offsets are relative to the end of the instruction
 
     // --- Constants & Variables ---
     OP_PUSH           = 0x10, // Push a 4-byte constant onto the stack
@@ -18,6 +19,7 @@ enum OpCode : uint8_t {
     OP_NEW_ARRAY      = 0x16, // Pop size, push array reference
     OP_STORE_ARRAY    = 0x17, // Pop value, Pop index, Pop array ref, Store
     OP_LOAD_ARRAY     = 0x18, // Pop index, Pop array ref, Push value
+    OP_ARRAY_LENGTH   = 0x19, // Pop array ref, Push its number of elements
 
     // --- Arithmetic & Logic ---
     OP_ADD            = 0x20,
@@ -25,18 +27,30 @@ enum OpCode : uint8_t {
     OP_MUL            = 0x22,
     OP_DIV            = 0x23,
     OP_LESS           = 0x24, // Pop b, Pop a, Push (a < b)
-    // Future: OP_EQUAL, OP_GREATER, OP_LESS
+    OP_EQUAL          = 0x25, // Pop b, Pop a, Push (a == b). Strings compare by content
 
     // --- Object-Oriented ---
     OP_NEW_INSTANCE   = 0x40, // Operand: Class ID (u32). Push instance ref.
     OP_GET_FIELD      = 0x41, // Operand: Field Index (u32). Pop ref, Push value.
     OP_SET_FIELD      = 0x42, // Operand: Field Index (u32). Pop value, Pop ref.
+    OP_INVOKE         = 0x43, // Operands: Method name constant (u32), Arg count incl. receiver (u8). Virtual call
+    OP_INSTANCE_OF    = 0x44, // Operand: Class ID (u32). Pop ref, Push 1 if it is an instance of the class or a subclass (0 for null)
+    OP_CHECK_CAST     = 0x45, // Operand: Class ID (u32). Peek ref, fail unless it is such an instance
 
     // --- Functions & Calls ---
     OP_CALL           = 0x30, // Call function at 4-byte address
     OP_RETURN         = 0x31, // Return from function
+    OP_ENTER          = 0x32, // Operand: Extra local slots (u32). Reserve them in the new frame
+    OP_THROW          = 0x33, // Pop an exception object and unwind to the nearest matching handler
+    OP_RETHROW        = 0x34, // Same, for an exception that was already caught (finally blocks, bare 'throw')
+    OP_MAKE_CLOSURE   = 0x35, // Operands: Function address (u32), Capture count (u8). Pop the captured values, push a closure ref
+    OP_CALL_INDIRECT  = 0x36, // Operand: Arg count (u8). Call the closure below the arguments, passing its captures after them
 
     // --- Utilities ---
     OP_POP            = 0x80, // Pop the top value from the stack and discard it
     OP_PRINT          = 0x81, // Pop the top value and print it to the console
+    OP_DUP            = 0x82, // Push a copy of the top value
+    OP_INT_TO_STRING  = 0x83, // Pop an int, push its decimal text as a string
+    OP_HASH_STRING    = 0x84, // Pop a string, push a non-negative hash of its content
+    OP_HASH_COMBINE   = 0x85, // Pop b, Pop a, Push a * 31 + b, kept non-negative
 };
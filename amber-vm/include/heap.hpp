@@ -7,11 +7,14 @@
 
 // Offset to distinguish Heap Objects from Constant Pool indices in negative handles
 constexpr int32_t HEAP_HANDLE_OFFSET = 0x40000000;
+// 'null': neither a heap handle nor a string constant
+constexpr int32_t NULL_REF = 0;
 
 enum class ObjType {
     STRING,
     ARRAY,
-    INSTANCE
+    INSTANCE,
+    CLOSURE
 };
 
 struct AmberObject {
@@ -37,6 +40,15 @@ struct InstanceObject : AmberObject {
     }
 };
 
+// A function value: the function's address plus the values it captured
+struct ClosureObject : AmberObject {
+    uint32_t address;
+    std::vector<int32_t> captures; // Passed after the call's own arguments
+    ClosureObject(uint32_t addr, std::vector<int32_t> values) : address(addr), captures(std::move(values)) {
+        type = ObjType::CLOSURE;
+    }
+};
+
 class Heap {
 public:
     std::vector<AmberObject*> objects; // Public for direct access by VM
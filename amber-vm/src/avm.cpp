@@ -18,7 +18,15 @@
         vm_stack.push_back(a op b); \
     } while (false)
 
-void execute(const std::vector<uint8_t>& bytecode, std::vector<std::string>& constants) {
+// Message of an Exception instance (its first field), or "" if it holds no string
+static std::string exception_message(const InstanceObject* obj, const std::vector<std::string>& constants) {
+    if (obj->fields.empty()) return "";
+    int32_t msg = obj->fields[0];
+    if (msg >= 0 || msg <= -HEAP_HANDLE_OFFSET) return "";
+    return constants[-msg - 1];
+}
+
+void execute(const std::vector<uint8_t>& bytecode, std::vector<std::string>& constants, const ClassTable& classes, const ExceptionTables& tables) {
     if (bytecode.empty()) {
         std::cout << "AVM Warning: No bytecode to execute." << std::endl;
         return; // Nothing to execute
@@ -43,305 +51,573 @@ void execute(const std::vector<uint8_t>& bytecode, std::vector<std::string>& con
 
     const uint8_t* ip = bytecode.data();
     const uint8_t* end = ip + bytecode.size();
+    const uint8_t* op_start = ip; // Start of the current instruction: where exceptions are thrown from
 
-    try {
-        while (ip < end) {
-            uint8_t instruction = *ip++;
-            switch (instruction) {
-                // --- Control Flow ---
-                case OP_HALT: {
-                    return; // End execution
-                }
-                case OP_JUMP: {
-                    int32_t offset;
-                    std::memcpy(&offset, ip, sizeof(int32_t));
-                    ip += 4;      // Consume the 4-byte offset from the instruction stream
-                    ip += offset; // Apply the relative jump
-                    break;
+    // Class ID of the built-in Exception class, used for runtime errors (-1 if the program has none)
+    int32_t exception_class = -1;
+    for (const auto& entry : classes) {
+        if (constants[entry.first] == "Exception") exception_class = entry.first;
+    }
+
+    auto is_subclass = [&](int32_t class_id, int32_t ancestor) {
+        while (class_id != -1) {
+            if (class_id == ancestor) return true;
+            auto cls = classes.find(class_id);
+            class_id = cls == classes.end() ? -1 : cls->second.parent_id;
+        }
+        return false;
+    };
+
+    // The class of a heap instance, or -1 for null and any other value
+    auto class_of = [&](int32_t ref) -> int32_t {
+        int32_t heap_idx = -ref - HEAP_HANDLE_OFFSET;
+        if (ref == NULL_REF || heap_idx < 0 || heap_idx >= (int32_t)gc.objects.size()) return -1;
+        InstanceObject* obj = dynamic_cast<InstanceObject*>(gc.objects[heap_idx]);
+        return obj ? obj->class_id : -1;
+    };
+
+    // Transfers control to the handler for an exception thrown at 'site', leaving frames until one
+    // handles it. Returns false if none does.
+    auto unwind = [&](int32_t exception, const uint8_t* site) {
+        const InstanceObject* obj = static_cast<InstanceObject*>(gc.objects[-exception - HEAP_HANDLE_OFFSET]);
+        for (;;) {
+            uint32_t address = site - bytecode.data();
+
+            // The narrowest range containing the site is the table of the function being run
+            const ExceptionTable* table = nullptr;
+            for (const auto& t : tables) {
+                if (address >= t.start && address < t.end && (!table || t.end - t.start < table->end - table->start)) table = &t;
+            }
+            if (table) {
+                for (const auto& h : table->handlers) {
+                    if (address >= h.start && address < h.end && (h.class_id == -1 || is_subclass(obj->class_id, h.class_id))) {
+                        vm_stack.resize(fp + table->frame_size); // Drop the temporaries of the interrupted code
+                        vm_stack.push_back(exception);
+                        ip = bytecode.data() + h.target;
+                        return true;
+                    }
                 }
-                case OP_JUMP_IF_FALSE: {
-                    int32_t offset;
-                    std::memcpy(&offset, ip, sizeof(int32_t));
-                    ip += 4; // Advance past the offset bytes
+            }
+
+            if (call_stack.empty()) return false;
+            // Leave the frame; the caller's CALL/INVOKE becomes the throw site
+            vm_stack.resize(fp);
+            site = call_stack.back() - 1;
+            call_stack.pop_back();
+            fp = fp_stack.back();
+            fp_stack.pop_back();
+        }
+    };
+
+    for (;;) {
+        try {
+            while (ip < end) {
+                op_start = ip;
+                uint8_t instruction = *ip++;
+                switch (instruction) {
+                    // --- Control Flow ---
+                    case OP_HALT: {
+                        return; // End execution
+                    }
+                    case OP_JUMP: {
+                        int32_t offset;
+                        std::memcpy(&offset, ip, sizeof(int32_t));
+                        ip += 4;      // Consume the 4-byte offset from the instruction stream
+                        ip += offset; // Apply the relative jump
+                        break;
+                    }
+                    case OP_JUMP_IF_FALSE: {
+                        int32_t offset;
+                        std::memcpy(&offset, ip, sizeof(int32_t));
+                        ip += 4; // Advance past the offset bytes
 
-                    if (vm_stack.empty()) throw std::runtime_error("Stack underflow during JUMP_IF_FALSE.");
-                    int32_t condition = vm_stack.back(); vm_stack.pop_back();
+                        if (vm_stack.empty()) throw std::runtime_error("Stack underflow during JUMP_IF_FALSE.");
+                        int32_t condition = vm_stack.back(); vm_stack.pop_back();
                     
-                    if (condition == 0) { // 0 is False
+                        if (condition == 0) { // 0 is False
+                            ip += offset;
+                        }
+                        break;
+                    }
+                    case OP_TABLE_SWITCH: {
+                        int32_t low;
+                        uint32_t count;
+                        int32_t offset;
+                        std::memcpy(&low, ip, sizeof(int32_t));
+                        std::memcpy(&count, ip + 4, sizeof(uint32_t));
+                        std::memcpy(&offset, ip + 8, sizeof(int32_t)); // Default
+                        const uint8_t* table = ip + 12;
+                        ip = table + count * 4; // End of the instruction
+
+                        if (vm_stack.empty()) throw std::runtime_error("Stack underflow during TABLE_SWITCH.");
+                        int64_t index = (int64_t)vm_stack.back() - low; vm_stack.pop_back();
+
+                        if (index >= 0 && index < count) {
+                            std::memcpy(&offset, table + index * 4, sizeof(int32_t));
+                        }
                         ip += offset;
+                        break;
                     }
-                    break;
-                }
 
-                // --- Constants & Variables ---
-                case OP_PUSH: {
-                    int32_t value;
-                    std::memcpy(&value, ip, sizeof(int32_t));
-                    vm_stack.push_back(value);
-                    ip += sizeof(int32_t);
-                    break;
-                }
-                case OP_LOAD_CONST: {
-                    int32_t index;
-                    std::memcpy(&index, ip, sizeof(int32_t));
-                    ip += sizeof(int32_t);
-                    // Store as negative index: -1 = index 0, -2 = index 1
-                    vm_stack.push_back(-index - 1);
-                    break;
-                }
-                case OP_STORE_GLOBAL: {
-                    int32_t index;
-                    std::memcpy(&index, ip, sizeof(int32_t));
-                    ip += sizeof(int32_t);
-
-                    if (vm_stack.empty()) throw std::runtime_error("Stack underflow during STORE.");
-                    int32_t val = vm_stack.back();
-                    vm_stack.pop_back();
-
-                    if (index >= globals.size()) globals.resize(index + 1);
-                    globals[index] = val;
-                    break;
-                }
-                case OP_LOAD_GLOBAL: {
-                    int32_t index;
-                    std::memcpy(&index, ip, sizeof(int32_t));
-                    ip += sizeof(int32_t);
-
-                    if (index < 0 || index >= globals.size()) throw std::runtime_error("Global variable index out of bounds.");
-                    vm_stack.push_back(globals[index]);
-                    break;
-                }
-                case OP_NEW_ARRAY: {
-                    if (vm_stack.empty()) throw std::runtime_error("Stack underflow during NEW_ARRAY.");
-                    int32_t size = vm_stack.back(); vm_stack.pop_back();
+                    // --- Constants & Variables ---
+                    case OP_PUSH: {
+                        int32_t value;
+                        std::memcpy(&value, ip, sizeof(int32_t));
+                        vm_stack.push_back(value);
+                        ip += sizeof(int32_t);
+                        break;
+                    }
+                    case OP_LOAD_CONST: {
+                        int32_t index;
+                        std::memcpy(&index, ip, sizeof(int32_t));
+                        ip += sizeof(int32_t);
+                        // Store as negative index: -1 = index 0, -2 = index 1
+                        vm_stack.push_back(-index - 1);
+                        break;
+                    }
+                    case OP_STORE_GLOBAL: {
+                        int32_t index;
+                        std::memcpy(&index, ip, sizeof(int32_t));
+                        ip += sizeof(int32_t);
+
+                        if (vm_stack.empty()) throw std::runtime_error("Stack underflow during STORE.");
+                        int32_t val = vm_stack.back();
+                        vm_stack.pop_back();
+
+                        if (index >= globals.size()) globals.resize(index + 1);
+                        globals[index] = val;
+                        break;
+                    }
+                    case OP_LOAD_GLOBAL: {
+                        int32_t index;
+                        std::memcpy(&index, ip, sizeof(int32_t));
+                        ip += sizeof(int32_t);
+
+                        if (index < 0 || index >= globals.size()) throw std::runtime_error("Global variable index out of bounds.");
+                        vm_stack.push_back(globals[index]);
+                        break;
+                    }
+                    case OP_NEW_ARRAY: {
+                        if (vm_stack.empty()) throw std::runtime_error("Stack underflow during NEW_ARRAY.");
+                        int32_t size = vm_stack.back(); vm_stack.pop_back();
                     
-                    ArrayObject* arr = new ArrayObject(size);
-                    int32_t heap_idx = gc.register_object(arr);
+                        ArrayObject* arr = new ArrayObject(size);
+                        int32_t heap_idx = gc.register_object(arr);
                     
-                    // Calculate handle using fixed offset: -(OFFSET + heap_idx)
-                    int32_t handle = -(HEAP_HANDLE_OFFSET + heap_idx);
-                    vm_stack.push_back(handle);
-                    break;
-                }
-                case OP_STORE_ARRAY: {
-                    if (vm_stack.size() < 3) throw std::runtime_error("Stack underflow during STORE_ARRAY.");
-                    int32_t val = vm_stack.back(); vm_stack.pop_back();
-                    int32_t idx = vm_stack.back(); vm_stack.pop_back();
-                    int32_t ref = vm_stack.back(); vm_stack.pop_back();
-
-                    int32_t abs_idx = -ref;
-                    int32_t heap_idx = abs_idx - HEAP_HANDLE_OFFSET;
+                        // Calculate handle using fixed offset: -(OFFSET + heap_idx)
+                        int32_t handle = -(HEAP_HANDLE_OFFSET + heap_idx);
+                        vm_stack.push_back(handle);
+                        break;
+                    }
+                    case OP_STORE_ARRAY: {
+                        if (vm_stack.size() < 3) throw std::runtime_error("Stack underflow during STORE_ARRAY.");
+                        int32_t val = vm_stack.back(); vm_stack.pop_back();
+                        int32_t idx = vm_stack.back(); vm_stack.pop_back();
+                        int32_t ref = vm_stack.back(); vm_stack.pop_back();
+                        if (ref == NULL_REF) throw std::runtime_error("Null reference: cannot index null.");
+
+                        int32_t abs_idx = -ref;
+                        int32_t heap_idx = abs_idx - HEAP_HANDLE_OFFSET;
                     
-                    if (heap_idx < 0 || heap_idx >= gc.objects.size()) throw std::runtime_error("Invalid array reference.");
-                    ArrayObject* arr = dynamic_cast<ArrayObject*>(gc.objects[heap_idx]);
-                    if (!arr) throw std::runtime_error("Reference is not an array.");
-                    if (idx < 0 || idx >= arr->data.size()) throw std::runtime_error("Array index out of bounds.");
+                        if (heap_idx < 0 || heap_idx >= gc.objects.size()) throw std::runtime_error("Invalid array reference.");
+                        ArrayObject* arr = dynamic_cast<ArrayObject*>(gc.objects[heap_idx]);
+                        if (!arr) throw std::runtime_error("Reference is not an array.");
+                        if (idx < 0 || idx >= arr->data.size()) throw std::runtime_error("Array index out of bounds.");
                     
-                    arr->data[idx] = val;
-                    break;
-                }
-                case OP_LOAD_ARRAY: {
-                    if (vm_stack.size() < 2) throw std::runtime_error("Stack underflow during LOAD_ARRAY.");
-                    int32_t idx = vm_stack.back(); vm_stack.pop_back();
-                    int32_t ref = vm_stack.back(); vm_stack.pop_back();
-
-                    int32_t abs_idx = -ref;
-                    int32_t heap_idx = abs_idx - HEAP_HANDLE_OFFSET;
+                        arr->data[idx] = val;
+                        break;
+                    }
+                    case OP_LOAD_ARRAY: {
+                        if (vm_stack.size() < 2) throw std::runtime_error("Stack underflow during LOAD_ARRAY.");
+                        int32_t idx = vm_stack.back(); vm_stack.pop_back();
+                        int32_t ref = vm_stack.back(); vm_stack.pop_back();
+                        if (ref == NULL_REF) throw std::runtime_error("Null reference: cannot index null.");
+
+                        int32_t abs_idx = -ref;
+                        int32_t heap_idx = abs_idx - HEAP_HANDLE_OFFSET;
                     
-                    if (heap_idx < 0 || heap_idx >= gc.objects.size()) throw std::runtime_error("Invalid array reference.");
-                    ArrayObject* arr = dynamic_cast<ArrayObject*>(gc.objects[heap_idx]);
-                    if (!arr) throw std::runtime_error("Reference is not an array.");
-                    if (idx < 0 || idx >= arr->data.size()) throw std::runtime_error("Array index out of bounds.");
+                        if (heap_idx < 0 || heap_idx >= gc.objects.size()) throw std::runtime_error("Invalid array reference.");
+                        ArrayObject* arr = dynamic_cast<ArrayObject*>(gc.objects[heap_idx]);
+                        if (!arr) throw std::runtime_error("Reference is not an array.");
+                        if (idx < 0 || idx >= arr->data.size()) throw std::runtime_error("Array index out of bounds.");
                     
-                    vm_stack.push_back(arr->data[idx]);
-                    break;
-                }
-                case OP_STORE_LOCAL: {
-                    int32_t index;
-                    std::memcpy(&index, ip, sizeof(int32_t));
-                    ip += sizeof(int32_t);
-
-                    if (vm_stack.empty()) throw std::runtime_error("Stack underflow during STORE_LOCAL.");
-                    int32_t val = vm_stack.back();
-                    vm_stack.pop_back();
+                        vm_stack.push_back(arr->data[idx]);
+                        break;
+                    }
+                    case OP_ARRAY_LENGTH: {
+                        if (vm_stack.empty()) throw std::runtime_error("Stack underflow during ARRAY_LENGTH.");
+                        int32_t ref = vm_stack.back(); vm_stack.pop_back();
+                        if (ref == NULL_REF) throw std::runtime_error("Null reference: cannot read the length of null.");
+
+                        int32_t heap_idx = -ref - HEAP_HANDLE_OFFSET;
+                        if (heap_idx < 0 || heap_idx >= gc.objects.size()) throw std::runtime_error("Invalid array reference.");
+                        ArrayObject* arr = dynamic_cast<ArrayObject*>(gc.objects[heap_idx]);
+                        if (!arr) throw std::runtime_error("Reference is not an array.");
+
+                        vm_stack.push_back((int32_t)arr->data.size());
+                        break;
+                    }
+                    case OP_STORE_LOCAL: {
+                        int32_t index;
+                        std::memcpy(&index, ip, sizeof(int32_t));
+                        ip += sizeof(int32_t);
+
+                        if (vm_stack.empty()) throw std::runtime_error("Stack underflow during STORE_LOCAL.");
+                        int32_t val = vm_stack.back();
+                        vm_stack.pop_back();
                     
-                    vm_stack[fp + index] = val;
-                    break;
-                }
-                case OP_LOAD_LOCAL: {
-                    int32_t index;
-                    std::memcpy(&index, ip, sizeof(int32_t));
-                    ip += sizeof(int32_t);
-                    vm_stack.push_back(vm_stack[fp + index]);
-                    break;
-                }
+                        vm_stack[fp + index] = val;
+                        break;
+                    }
+                    case OP_LOAD_LOCAL: {
+                        int32_t index;
+                        std::memcpy(&index, ip, sizeof(int32_t));
+                        ip += sizeof(int32_t);
+                        vm_stack.push_back(vm_stack[fp + index]);
+                        break;
+                    }
                 
-                // --- Object-Oriented ---
-                case OP_NEW_INSTANCE: {
-                    int32_t class_name_idx;
-                    std::memcpy(&class_name_idx, ip, sizeof(int32_t)); ip += 4;
-                    int32_t field_count;
-                    std::memcpy(&field_count, ip, sizeof(int32_t)); ip += 4;
-
-                    InstanceObject* obj = new InstanceObject(class_name_idx, field_count);
-                    int32_t heap_idx = gc.register_object(obj);
+                    // --- Object-Oriented ---
+                    case OP_NEW_INSTANCE: {
+                        int32_t class_name_idx;
+                        std::memcpy(&class_name_idx, ip, sizeof(int32_t)); ip += 4;
+                        int32_t field_count;
+                        std::memcpy(&field_count, ip, sizeof(int32_t)); ip += 4;
+
+                        InstanceObject* obj = new InstanceObject(class_name_idx, field_count);
+                        int32_t heap_idx = gc.register_object(obj);
                     
-                    // Push handle
-                    int32_t handle = -(HEAP_HANDLE_OFFSET + heap_idx);
-                    vm_stack.push_back(handle);
-                    break;
-                }
-                case OP_GET_FIELD: {
-                    int32_t field_idx;
-                    std::memcpy(&field_idx, ip, sizeof(int32_t)); ip += 4;
+                        // Push handle
+                        int32_t handle = -(HEAP_HANDLE_OFFSET + heap_idx);
+                        vm_stack.push_back(handle);
+                        break;
+                    }
+                    case OP_GET_FIELD: {
+                        int32_t field_idx;
+                        std::memcpy(&field_idx, ip, sizeof(int32_t)); ip += 4;
 
-                    if (vm_stack.empty()) throw std::runtime_error("Stack underflow during GET_FIELD.");
-                    int32_t ref = vm_stack.back(); vm_stack.pop_back();
+                        if (vm_stack.empty()) throw std::runtime_error("Stack underflow during GET_FIELD.");
+                        int32_t ref = vm_stack.back(); vm_stack.pop_back();
+                        if (ref == NULL_REF) throw std::runtime_error("Null reference: cannot read a field of null.");
 
-                    int32_t heap_idx = -ref - HEAP_HANDLE_OFFSET;
-                    if (heap_idx < 0 || heap_idx >= gc.objects.size()) throw std::runtime_error("Invalid instance reference.");
+                        int32_t heap_idx = -ref - HEAP_HANDLE_OFFSET;
+                        if (heap_idx < 0 || heap_idx >= gc.objects.size()) throw std::runtime_error("Invalid instance reference.");
                     
-                    InstanceObject* obj = dynamic_cast<InstanceObject*>(gc.objects[heap_idx]);
-                    if (!obj) throw std::runtime_error("Reference is not an instance.");
-                    if (field_idx < 0 || field_idx >= obj->fields.size()) throw std::runtime_error("Field index out of bounds.");
+                        InstanceObject* obj = dynamic_cast<InstanceObject*>(gc.objects[heap_idx]);
+                        if (!obj) throw std::runtime_error("Reference is not an instance.");
+                        if (field_idx < 0 || field_idx >= obj->fields.size()) throw std::runtime_error("Field index out of bounds.");
 
-                    vm_stack.push_back(obj->fields[field_idx]);
-                    break;
-                }
-                // Note: OP_SET_FIELD logic is usually handled by Stmt::Assign in the compiler, 
-                // but if we have a specific opcode for it:
-                case OP_SET_FIELD: {
-                    int32_t field_idx;
-                    std::memcpy(&field_idx, ip, sizeof(int32_t)); ip += 4;
-
-                    if (vm_stack.size() < 2) throw std::runtime_error("Stack underflow during SET_FIELD.");
-                    int32_t val = vm_stack.back(); vm_stack.pop_back();
-                    int32_t ref = vm_stack.back(); vm_stack.pop_back();
-
-                    int32_t heap_idx = -ref - HEAP_HANDLE_OFFSET;
-                    if (heap_idx < 0 || heap_idx >= gc.objects.size()) throw std::runtime_error("Invalid instance reference.");
+                        vm_stack.push_back(obj->fields[field_idx]);
+                        break;
+                    }
+                    // Note: OP_SET_FIELD logic is usually handled by Stmt::Assign in the compiler, 
+                    // but if we have a specific opcode for it:
+                    case OP_SET_FIELD: {
+                        int32_t field_idx;
+                        std::memcpy(&field_idx, ip, sizeof(int32_t)); ip += 4;
+
+                        if (vm_stack.size() < 2) throw std::runtime_error("Stack underflow during SET_FIELD.");
+                        int32_t val = vm_stack.back(); vm_stack.pop_back();
+                        int32_t ref = vm_stack.back(); vm_stack.pop_back();
+                        if (ref == NULL_REF) throw std::runtime_error("Null reference: cannot write a field of null.");
+
+                        int32_t heap_idx = -ref - HEAP_HANDLE_OFFSET;
+                        if (heap_idx < 0 || heap_idx >= gc.objects.size()) throw std::runtime_error("Invalid instance reference.");
                     
-                    InstanceObject* obj = dynamic_cast<InstanceObject*>(gc.objects[heap_idx]);
-                    if (!obj) throw std::runtime_error("Reference is not an instance.");
-                    if (field_idx < 0 || field_idx >= obj->fields.size()) throw std::runtime_error("Field index out of bounds.");
+                        InstanceObject* obj = dynamic_cast<InstanceObject*>(gc.objects[heap_idx]);
+                        if (!obj) throw std::runtime_error("Reference is not an instance.");
+                        if (field_idx < 0 || field_idx >= obj->fields.size()) throw std::runtime_error("Field index out of bounds.");
 
-                    obj->fields[field_idx] = val;
-                    break;
-                }
+                        obj->fields[field_idx] = val;
+                        break;
+                    }
+
+                    case OP_INVOKE: {
+                        int32_t name_idx;
+                        std::memcpy(&name_idx, ip, sizeof(int32_t)); ip += 4;
+                        uint8_t arg_count = *ip++;
 
-                // --- Arithmetic & Logic ---
-                case OP_ADD: { // Overloaded for integers and strings
-                    if (vm_stack.size() < 2) throw std::runtime_error("Stack underflow during ADD.");
-                    int32_t b = vm_stack.back(); vm_stack.pop_back();
-                    int32_t a = vm_stack.back(); vm_stack.pop_back();
+                        if (arg_count == 0 || vm_stack.size() < arg_count) throw std::runtime_error("Stack underflow during INVOKE.");
+                        int32_t ref = vm_stack[vm_stack.size() - arg_count]; // Receiver sits below the arguments
+                        if (ref == NULL_REF) throw std::runtime_error("Null reference: cannot call a method on null.");
 
-                    // Check if both are strings (negative values)
-                    if (a < 0 && b < 0) {
-                        const std::string& str_a = constants[-a - 1];
-                        const std::string& str_b = constants[-b - 1];
+                        int32_t heap_idx = -ref - HEAP_HANDLE_OFFSET;
+                        if (heap_idx < 0 || heap_idx >= gc.objects.size()) throw std::runtime_error("Invalid instance reference.");
+                    
+                        InstanceObject* obj = dynamic_cast<InstanceObject*>(gc.objects[heap_idx]);
+                        if (!obj) throw std::runtime_error("Reference is not an instance.");
+
+                        // Dispatch on the runtime class of the receiver
+                        auto cls = classes.find(obj->class_id);
+                        if (cls == classes.end()) throw std::runtime_error("Unknown class of receiver.");
+                        auto method = cls->second.methods.find(name_idx);
+                        if (method == cls->second.methods.end()) {
+                            throw std::runtime_error("Method '" + constants[name_idx] + "' not found on class '" + constants[obj->class_id] + "'.");
+                        }
+
+                        fp_stack.push_back(fp);
+                        fp = vm_stack.size() - arg_count; // FP points to the receiver ('this')
+
+                        call_stack.push_back(ip); // Save return address
+                        ip = bytecode.data() + method->second;
+                        break;
+                    }
+
+                    case OP_INSTANCE_OF: {
+                        int32_t class_id;
+                        std::memcpy(&class_id, ip, sizeof(int32_t)); ip += 4;
+
+                        if (vm_stack.empty()) throw std::runtime_error("Stack underflow during INSTANCE_OF.");
+                        int32_t ref = vm_stack.back(); vm_stack.pop_back();
+                        vm_stack.push_back(is_subclass(class_of(ref), class_id) ? 1 : 0);
+                        break;
+                    }
+                    case OP_CHECK_CAST: {
+                        int32_t class_id;
+                        std::memcpy(&class_id, ip, sizeof(int32_t)); ip += 4;
+
+                        if (vm_stack.empty()) throw std::runtime_error("Stack underflow during CHECK_CAST.");
+                        int32_t ref = vm_stack.back();
+                        int32_t actual = class_of(ref); // Casts to a nullable type skip the check for null
+                        if (!is_subclass(actual, class_id)) {
+                            std::string from = ref == NULL_REF ? "null" : actual == -1 ? "value" : "'" + constants[actual] + "'";
+                            throw std::runtime_error("Cannot cast " + from + " to '" + constants[class_id] + "'.");
+                        }
+                        break;
+                    }
+
+                    // --- Arithmetic & Logic ---
+                    case OP_ADD: { // Overloaded for integers and strings
+                        if (vm_stack.size() < 2) throw std::runtime_error("Stack underflow during ADD.");
+                        int32_t b = vm_stack.back(); vm_stack.pop_back();
+                        int32_t a = vm_stack.back(); vm_stack.pop_back();
+
+                        // Check if both are strings (negative values)
+                        if (a < 0 && b < 0) {
+                            const std::string& str_a = constants[-a - 1];
+                            const std::string& str_b = constants[-b - 1];
                         
-                        // Create new concatenated string
-                        std::string result_str = str_a + str_b;
+                            // Create new concatenated string
+                            std::string result_str = str_a + str_b;
                         
-                        // Add to constants pool (this is a memory leak until GC)
-                        constants.push_back(result_str);
+                            // Add to constants pool (this is a memory leak until GC)
+                            constants.push_back(result_str);
 
-                        // Trigger GC (Simulate allocation pressure)
-                        gc.collect(vm_stack, globals, constants.size());
+                            // Trigger GC (Simulate allocation pressure)
+                            gc.collect(vm_stack, globals, constants.size());
                         
-                        // Push new index onto stack
-                        int32_t new_index = constants.size() - 1;
-                        vm_stack.push_back(-new_index - 1);
-                    } 
-                    // Check if both are integers (non-negative values)
-                    else if (a >= 0 && b >= 0) {
-                        vm_stack.push_back(a + b);
-                    } 
-                    // Mixed types
-                    else {
-                        throw std::runtime_error("Type mismatch: Cannot add a string and an integer.");
-                    }
-                    break;
-                }
-                case OP_SUB: BINARY_OP(-); break;
-                case OP_MUL: BINARY_OP(*); break;
-                case OP_DIV: {
-                    if (vm_stack.size() < 2) throw std::runtime_error("Stack underflow during DIV.");
-                    int32_t b = vm_stack.back(); vm_stack.pop_back();
-                    if (b == 0) throw std::runtime_error("Division by zero.");
-                    int32_t a = vm_stack.back(); vm_stack.pop_back();
-                    vm_stack.push_back(a / b);
-                    break;
-                }
-                case OP_LESS: {
-                    if (vm_stack.size() < 2) throw std::runtime_error("Stack underflow during LESS.");
-                    int32_t b = vm_stack.back(); vm_stack.pop_back();
-                    int32_t a = vm_stack.back(); vm_stack.pop_back();
-                    vm_stack.push_back(a < b ? 1 : 0);
-                    break;
-                }
+                            // Push new index onto stack
+                            int32_t new_index = constants.size() - 1;
+                            vm_stack.push_back(-new_index - 1);
+                        } 
+                        // Check if both are integers (non-negative values)
+                        else if (a >= 0 && b >= 0) {
+                            vm_stack.push_back(a + b);
+                        } 
+                        // Mixed types
+                        else {
+                            throw std::runtime_error("Type mismatch: Cannot add a string and an integer.");
+                        }
+                        break;
+                    }
+                    case OP_SUB: BINARY_OP(-); break;
+                    case OP_MUL: BINARY_OP(*); break;
+                    case OP_DIV: {
+                        if (vm_stack.size() < 2) throw std::runtime_error("Stack underflow during DIV.");
+                        int32_t b = vm_stack.back(); vm_stack.pop_back();
+                        if (b == 0) throw std::runtime_error("Division by zero.");
+                        int32_t a = vm_stack.back(); vm_stack.pop_back();
+                        vm_stack.push_back(a / b);
+                        break;
+                    }
+                    case OP_LESS: {
+                        if (vm_stack.size() < 2) throw std::runtime_error("Stack underflow during LESS.");
+                        int32_t b = vm_stack.back(); vm_stack.pop_back();
+                        int32_t a = vm_stack.back(); vm_stack.pop_back();
+                        vm_stack.push_back(a < b ? 1 : 0);
+                        break;
+                    }
 
-                // --- Functions & Calls ---
-                case OP_CALL: {
-                    int32_t target_offset;
-                    std::memcpy(&target_offset, ip, sizeof(int32_t));
-                    ip += 4;
+                    case OP_EQUAL: {
+                        if (vm_stack.size() < 2) throw std::runtime_error("Stack underflow during EQUAL.");
+                        int32_t b = vm_stack.back(); vm_stack.pop_back();
+                        int32_t a = vm_stack.back(); vm_stack.pop_back();
+
+                        // Strings live in the constant pool (-1 .. -HEAP_HANDLE_OFFSET+1); equal text may sit at different indices
+                        bool a_str = a < 0 && a > -HEAP_HANDLE_OFFSET;
+                        bool b_str = b < 0 && b > -HEAP_HANDLE_OFFSET;
+                        if (a_str && b_str) {
+                            vm_stack.push_back(constants[-a - 1] == constants[-b - 1] ? 1 : 0);
+                        } else {
+                            vm_stack.push_back(a == b ? 1 : 0);
+                        }
+                        break;
+                    }
 
-                    uint8_t arg_count = *ip++;
+                    // --- Functions & Calls ---
+                    case OP_CALL: {
+                        int32_t target_offset;
+                        std::memcpy(&target_offset, ip, sizeof(int32_t));
+                        ip += 4;
+
+                        uint8_t arg_count = *ip++;
                     
-                    if (vm_stack.size() < arg_count) throw std::runtime_error("Stack underflow during CALL.");
+                        if (vm_stack.size() < arg_count) throw std::runtime_error("Stack underflow during CALL.");
                     
-                    fp_stack.push_back(fp);
-                    // FP points to the first argument
-                    fp = vm_stack.size() - arg_count; 
+                        fp_stack.push_back(fp);
+                        // FP points to the first argument
+                        fp = vm_stack.size() - arg_count; 
 
-                    call_stack.push_back(ip); // Save return address
-                    ip = bytecode.data() + target_offset; // Jump to function
-                    break;
-                }
-                case OP_RETURN: {
-                    if (call_stack.empty()) return; // Or halt
+                        call_stack.push_back(ip); // Save return address
+                        ip = bytecode.data() + target_offset; // Jump to function
+                        break;
+                    }
+                    case OP_ENTER: {
+                        int32_t local_count;
+                        std::memcpy(&local_count, ip, sizeof(int32_t));
+                        ip += 4;
+
+                        // Zero-fill the slots for locals declared in the function body
+                        vm_stack.resize(vm_stack.size() + local_count, 0);
+                        break;
+                    }
+                    case OP_MAKE_CLOSURE: {
+                        uint32_t address;
+                        std::memcpy(&address, ip, sizeof(uint32_t)); ip += 4;
+                        uint8_t capture_count = *ip++;
+
+                        if (vm_stack.size() < capture_count) throw std::runtime_error("Stack underflow during MAKE_CLOSURE.");
+                        std::vector<int32_t> captures(vm_stack.end() - capture_count, vm_stack.end());
+                        vm_stack.resize(vm_stack.size() - capture_count);
+
+                        int32_t heap_idx = gc.register_object(new ClosureObject(address, std::move(captures)));
+                        vm_stack.push_back(-(HEAP_HANDLE_OFFSET + heap_idx));
+                        break;
+                    }
+                    case OP_CALL_INDIRECT: {
+                        uint8_t arg_count = *ip++;
+
+                        if (vm_stack.size() < arg_count + 1u) throw std::runtime_error("Stack underflow during CALL_INDIRECT.");
+                        size_t callee_pos = vm_stack.size() - arg_count - 1; // The closure sits below the arguments
+                        if (vm_stack[callee_pos] == NULL_REF) throw std::runtime_error("Null reference: cannot call null.");
+                        int32_t heap_idx = -vm_stack[callee_pos] - HEAP_HANDLE_OFFSET;
+                        if (heap_idx < 0 || heap_idx >= gc.objects.size()) throw std::runtime_error("Invalid function reference.");
+                        ClosureObject* closure = dynamic_cast<ClosureObject*>(gc.objects[heap_idx]);
+                        if (!closure) throw std::runtime_error("Reference is not a function.");
+
+                        // Frame layout: arguments, then the captured values
+                        vm_stack.erase(vm_stack.begin() + callee_pos);
+                        vm_stack.insert(vm_stack.end(), closure->captures.begin(), closure->captures.end());
+
+                        fp_stack.push_back(fp);
+                        fp = callee_pos; // FP points to the first argument
+
+                        call_stack.push_back(ip); // Save return address
+                        ip = bytecode.data() + closure->address;
+                        break;
+                    }
+                    case OP_THROW:
+                    case OP_RETHROW: {
+                        if (vm_stack.empty()) throw std::runtime_error("Stack underflow during THROW.");
+                        int32_t exception = vm_stack.back(); vm_stack.pop_back();
+
+                        int32_t heap_idx = -exception - HEAP_HANDLE_OFFSET;
+                        if (heap_idx < 0 || heap_idx >= gc.objects.size() || !dynamic_cast<InstanceObject*>(gc.objects[heap_idx])) {
+                            throw std::runtime_error("Only objects can be thrown.");
+                        }
+                        if (!unwind(exception, op_start)) {
+                            const InstanceObject* obj = static_cast<InstanceObject*>(gc.objects[heap_idx]);
+                            std::cerr << "AVM Runtime Error: Uncaught " << constants[obj->class_id] << ": " << exception_message(obj, constants) << std::endl;
+                            return;
+                        }
+                        break;
+                    }
+                    case OP_RETURN: {
+                        if (call_stack.empty()) return; // Or halt
                     
-                    int32_t result = vm_stack.back(); vm_stack.pop_back();
+                        int32_t result = vm_stack.back(); vm_stack.pop_back();
                     
-                    // Restore stack (remove args/locals)
-                    vm_stack.resize(fp); 
-                    vm_stack.push_back(result); // Push result back
-
-                    ip = call_stack.back();
-                    call_stack.pop_back();
-                    fp = fp_stack.back();
-                    fp_stack.pop_back();
-                    break;
-                }
+                        // Restore stack (remove args/locals)
+                        vm_stack.resize(fp); 
+                        vm_stack.push_back(result); // Push result back
+
+                        ip = call_stack.back();
+                        call_stack.pop_back();
+                        fp = fp_stack.back();
+                        fp_stack.pop_back();
+                        break;
+                    }
 
-                // --- Utilities ---
-                case OP_POP: vm_stack.pop_back(); break;
-                case OP_PRINT: {
-                    if (vm_stack.empty()) throw std::runtime_error("Stack underflow during PRINT.");
-                    int32_t val = vm_stack.back();
-                    vm_stack.pop_back();
+                    // --- Utilities ---
+                    case OP_POP: vm_stack.pop_back(); break;
+                    case OP_DUP: {
+                        if (vm_stack.empty()) throw std::runtime_error("Stack underflow during DUP.");
+                        vm_stack.push_back(vm_stack.back());
+                        break;
+                    }
+                    case OP_INT_TO_STRING: {
+                        if (vm_stack.empty()) throw std::runtime_error("Stack underflow during INT_TO_STRING.");
+                        int32_t val = vm_stack.back(); vm_stack.pop_back();
+
+                        constants.push_back(std::to_string(val));
+                        gc.collect(vm_stack, globals, constants.size());
+                        int32_t new_index = constants.size() - 1;
+                        vm_stack.push_back(-new_index - 1);
+                        break;
+                    }
+                    case OP_HASH_STRING: {
+                        if (vm_stack.empty()) throw std::runtime_error("Stack underflow during HASH_STRING.");
+                        int32_t val = vm_stack.back(); vm_stack.pop_back();
+                        if (val >= 0 || val <= -HEAP_HANDLE_OFFSET) throw std::runtime_error("HASH_STRING expects a string.");
+
+                        uint32_t hash = 0;
+                        for (unsigned char c : constants[-val - 1]) hash = hash * 31 + c;
+                        vm_stack.push_back(hash & 0x3FFFFFFF); // Non-negative, so it never reads as a string
+                        break;
+                    }
+                    case OP_HASH_COMBINE: {
+                        if (vm_stack.size() < 2) throw std::runtime_error("Stack underflow during HASH_COMBINE.");
+                        uint32_t b = vm_stack.back(); vm_stack.pop_back();
+                        uint32_t a = vm_stack.back(); vm_stack.pop_back();
+                        vm_stack.push_back((a * 31 + b) & 0x3FFFFFFF);
+                        break;
+                    }
+                    case OP_PRINT: {
+                        if (vm_stack.empty()) throw std::runtime_error("Stack underflow during PRINT.");
+                        int32_t val = vm_stack.back();
+                        vm_stack.pop_back();
                     
-                    // Check if it's a Heap Object Handle first
-                    if (val <= -HEAP_HANDLE_OFFSET) {
-                        std::cout << "Amber Out: [Heap Object " << (-val - HEAP_HANDLE_OFFSET) << "]" << std::endl;
-                    } else if (val < 0) {
-                        // It's a String Constant
-                        size_t idx = -val - 1;
-                        if (idx < constants.size()) std::cout << "Amber Out: " << constants[idx] << std::endl;
-                        else std::cout << "Amber Out: <Invalid String Index>" << std::endl;
-                    } else {
-                        std::cout << "Amber Out: " << val << std::endl;
-                    }
-                    break;
-                }
+                        // Check if it's a Heap Object Handle first
+                        if (val <= -HEAP_HANDLE_OFFSET) {
+                            std::cout << "Amber Out: [Heap Object " << (-val - HEAP_HANDLE_OFFSET) << "]" << std::endl;
+                        } else if (val < 0) {
+                            // It's a String Constant
+                            size_t idx = -val - 1;
+                            if (idx < constants.size()) std::cout << "Amber Out: " << constants[idx] << std::endl;
+                            else std::cout << "Amber Out: <Invalid String Index>" << std::endl;
+                        } else {
+                            std::cout << "Amber Out: " << val << std::endl;
+                        }
+                        break;
+                    }
 
-                default: {
-                    throw std::runtime_error("Unknown opcode encountered.");
+                    default: {
+                        throw std::runtime_error("Unknown opcode encountered.");
+                    }
                 }
             }
+            return;
+        } catch (const std::runtime_error& e) {
+            // Runtime errors are raised as Exception objects, so programs can recover from them
+            if (exception_class != -1) {
+                constants.push_back(e.what());
+                InstanceObject* obj = new InstanceObject(exception_class, 1);
+                obj->fields[0] = -(int32_t)constants.size();
+                int32_t exception = -(HEAP_HANDLE_OFFSET + gc.register_object(obj));
+                if (unwind(exception, op_start)) continue;
+            }
+            std::cerr << "AVM Runtime Error: " << e.what() << std::endl;
+            return;
         }
-    } catch (const std::runtime_error& e) {
-        std::cerr << "AVM Runtime Error: " << e.what() << std::endl;
     }
 }
\ No newline at end of file
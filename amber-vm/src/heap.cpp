@@ -51,6 +51,16 @@ void Heap::mark(AmberObject* obj, size_t constant_pool_size) {
                 }
             }
         }
+    } else if (obj->type == ObjType::CLOSURE) {
+        ClosureObject* closure = static_cast<ClosureObject*>(obj);
+        for (int32_t val : closure->captures) {
+            if (val <= -HEAP_HANDLE_OFFSET) {
+                size_t heap_idx = -val - HEAP_HANDLE_OFFSET;
+                if (heap_idx < objects.size()) {
+                    mark(objects[heap_idx], constant_pool_size);
+                }
+            }
+        }
     }
 }
 
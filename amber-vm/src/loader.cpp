@@ -4,7 +4,9 @@
 #include <cstring>
 
 namespace Loader {
-    bool load(const char* filename, std::vector<uint8_t>& bytecode, std::vector<std::string>& constants) {
+    constexpr uint16_t SUPPORTED_VERSION = 3;
+
+    bool load(const char* filename, std::vector<uint8_t>& bytecode, std::vector<std::string>& constants, ClassTable& classes, ExceptionTables& tables) {
         std::ifstream file(filename, std::ios::binary);
         if (!file) {
             std::cerr << "Error: Could not open file " << filename << std::endl;
@@ -19,8 +21,14 @@ namespace Loader {
             return false;
         }
 
-        // 2. Skip Version (2 bytes) and Entry Point (4 bytes)
-        file.ignore(6);
+        // 2. Check Version (2 bytes) and skip Entry Point (4 bytes)
+        uint16_t version;
+        file.read(reinterpret_cast<char*>(&version), 2);
+        if (version != SUPPORTED_VERSION) {
+            std::cerr << "Error: Unsupported bytecode version " << version << " (expected " << SUPPORTED_VERSION << "). Recompile the source." << std::endl;
+            return false;
+        }
+        file.ignore(4);
 
         // 3. Read Constant Pool
         uint32_t poolCount;
@@ -35,11 +43,52 @@ namespace Loader {
             constants.push_back(s);
         }
 
-        // 4. Read Code Length
+        // 4. Read Class Table
+        uint32_t classCount;
+        file.read(reinterpret_cast<char*>(&classCount), 4);
+        for (uint32_t i = 0; i < classCount; ++i) {
+            int32_t classId;
+            ClassEntry entry;
+            uint32_t methodCount;
+            file.read(reinterpret_cast<char*>(&classId), 4);
+            file.read(reinterpret_cast<char*>(&entry.parent_id), 4);
+            file.read(reinterpret_cast<char*>(&methodCount), 4);
+            for (uint32_t m = 0; m < methodCount; ++m) {
+                int32_t nameIdx;
+                uint32_t address;
+                file.read(reinterpret_cast<char*>(&nameIdx), 4);
+                file.read(reinterpret_cast<char*>(&address), 4);
+                entry.methods[nameIdx] = address;
+            }
+            classes[classId] = entry;
+        }
+
+        // 5. Read Exception Tables (one per function, plus the top level)
+        uint32_t tableCount;
+        file.read(reinterpret_cast<char*>(&tableCount), 4);
+        for (uint32_t i = 0; i < tableCount; ++i) {
+            ExceptionTable table;
+            uint32_t handlerCount;
+            file.read(reinterpret_cast<char*>(&table.start), 4);
+            file.read(reinterpret_cast<char*>(&table.end), 4);
+            file.read(reinterpret_cast<char*>(&table.frame_size), 4);
+            file.read(reinterpret_cast<char*>(&handlerCount), 4);
+            for (uint32_t h = 0; h < handlerCount; ++h) {
+                Handler handler;
+                file.read(reinterpret_cast<char*>(&handler.start), 4);
+                file.read(reinterpret_cast<char*>(&handler.end), 4);
+                file.read(reinterpret_cast<char*>(&handler.target), 4);
+                file.read(reinterpret_cast<char*>(&handler.class_id), 4);
+                table.handlers.push_back(handler);
+            }
+            tables.push_back(table);
+        }
+
+        // 6. Read Code Length
         uint32_t codeLength;
         file.read(reinterpret_cast<char*>(&codeLength), 4);
 
-        // 5. Read Bytecode
+        // 7. Read Bytecode
         if (codeLength > 0) {
             bytecode.resize(codeLength);
             file.read(reinterpret_cast<char*>(bytecode.data()), codeLength);
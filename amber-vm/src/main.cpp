@@ -10,10 +10,12 @@ int main(int argc, char* argv[]) {
 
     std::vector<uint8_t> bytecode;
     std::vector<std::string> constants;
-    if (!Loader::load(argv[1], bytecode, constants)) {
+    ClassTable classes;
+    ExceptionTables tables;
+    if (!Loader::load(argv[1], bytecode, constants, classes, tables)) {
         return 1;
     }
 
-    execute(bytecode, constants);
+    execute(bytecode, constants, classes, tables);
     return 0;
 }
\ No newline at end of file